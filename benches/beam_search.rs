@@ -1,4 +1,4 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
 use tetrizz::beam_search::*;
 use tetrizz::data::*;
 use tetrizz::eval::*;
@@ -147,30 +147,49 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         Piece::Z,
     ];
     let eval = Eval::from([1.0; 14]);
-    let mut game = Game::new(Some(hold));
-    game.board.cols = [
-        Column(2047),
-        Column(1023),
-        Column(511),
-        Column(0),
-        Column(0),
-        Column(340),
-        Column(8062),
-        Column(16383),
-        Column(5119),
-        Column(2047),
-    ];
-    c.bench_function("beam search test", |b| {
+    let game = bench_game(hold);
+    let config = SearchConfig {
+        depth: 8,
+        width: 1000,
+        preview: 7,
+        seed: Some(0),
+        ..SearchConfig::default()
+    };
+    // the search is seeded, so every iteration evaluates the same number of nodes and the
+    // throughput below comes out in nodes/sec
+    let search_loc = candidates(&game, queue[0], &config);
+    let nodes = search_results(&game, &search_loc, &queue, &eval, &config)
+        .unwrap()
+        .stats
+        .nodes;
+    let mut group = c.benchmark_group("beam search");
+    group.throughput(Throughput::Elements(nodes as u64));
+    group.bench_function("beam search test", |b| {
+        b.iter(|| {
+            search(
+                std::hint::black_box(&game),
+                &queue,
+                &eval,
+                std::hint::black_box(&config),
+            )
+        })
+    });
+    // same search with movegen going through the cache
+    let cached = SearchConfig {
+        movegen_cache: Some(1 << 16),
+        ..config
+    };
+    group.bench_function("beam search cached", |b| {
         b.iter(|| {
             search(
                 std::hint::black_box(&game),
-                queue.clone(),
+                &queue,
                 &eval,
-                std::hint::black_box(8),
-                1000,
+                std::hint::black_box(&cached),
             )
         })
     });
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
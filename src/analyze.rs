@@ -0,0 +1,140 @@
+use crate::beam_search::*;
+use crate::data::*;
+use crate::eval::Eval;
+use crate::fumen;
+
+use serde::{Deserialize, Serialize};
+
+// a board written either as text rows (see Board's FromStr) or as a v115 fumen
+pub fn parse_board(s: &str) -> Result<Board, ParseError> {
+    if s.trim().get(1..5) == Some("115@") {
+        fumen::decode(s)
+    } else {
+        s.parse()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Candidate {
+    pub loc: PieceLocation,
+    pub info: PlacementInfo,
+    // the board right after this move
+    pub board: Board,
+    // score of the best line the search found starting with this move, and what it's made of
+    pub score: f32,
+    pub reward: f32,
+    pub value: f32,
+    // that best line, starting with this move
+    pub line: Vec<(PieceLocation, PlacementInfo)>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Analysis {
+    // best first
+    pub candidates: Vec<Candidate>,
+    pub stats: SearchStats,
+}
+
+// searches the position and ranks every first move by the best line the beam kept for it. moves
+// whose lines all got pushed out of the beam don't show up, a root_quota keeps more of them around
+pub fn analyze(
+    game: &Game,
+    queue: &[Piece],
+    eval: &Eval,
+    config: &SearchConfig,
+    top: usize,
+) -> Result<Analysis, SearchError> {
+    let Some(&current) = queue.first() else {
+        return Err(SearchError::EmptyQueue);
+    };
+    if game.board.topped_out(current) {
+        return Err(SearchError::ToppedOut);
+    }
+    let search_loc = candidates(game, current, config);
+    let results = search_results(game, &search_loc, queue, eval, config)?;
+
+    let mut best: Vec<Option<&Node>> = vec![None; search_loc.len()];
+    for node in &results.nodes {
+        if best[node.id].is_none_or(|b| node.score > b.score) {
+            best[node.id] = Some(node);
+        }
+    }
+    let mut best: Vec<&Node> = best.into_iter().flatten().collect();
+    // nodes order worst first, so this puts the highest score first
+    best.sort();
+
+    let candidates = best
+        .into_iter()
+        .take(top)
+        .map(|node| {
+            let line = results.path(node);
+            let (loc, _) = line[0];
+            let mut after = *game;
+            let info = after.advance(current, loc);
+            Candidate {
+                loc,
+                info,
+                board: after.board,
+                score: *node.score,
+                reward: node.reward,
+                value: node.value,
+                line,
+            }
+        })
+        .collect();
+    Ok(Analysis {
+        candidates,
+        stats: results.stats,
+    })
+}
+
+// a move someone played next to what the bot would have played from the same position, each with
+// its Eval::breakdown
+#[derive(Serialize, Debug, Clone)]
+pub struct Comparison {
+    pub human: PieceLocation,
+    pub human_terms: Vec<(&'static str, f32)>,
+    // None if the search failed
+    pub bot: Option<(PieceLocation, Vec<(&'static str, f32)>)>,
+}
+
+impl Comparison {
+    // true if both placements fill the same cells
+    pub fn agrees(&self) -> bool {
+        self.bot.as_ref().is_some_and(|(bot, _)| {
+            let (mut a, mut b) = (self.human.blocks(), bot.blocks());
+            a.sort();
+            b.sort();
+            a == b
+        })
+    }
+
+    // how much more the human's move scored than the bot's, usually negative
+    pub fn delta(&self) -> Option<f32> {
+        let total = |terms: &[(&str, f32)]| terms.iter().map(|&(_, v)| v).sum::<f32>();
+        let (_, bot_terms) = self.bot.as_ref()?;
+        Some(total(&self.human_terms) - total(bot_terms))
+    }
+}
+
+// grades `played` against a search from the same position. queue starts with the current piece
+pub fn compare(
+    game: &Game,
+    queue: &[Piece],
+    played: PieceLocation,
+    eval: &Eval,
+    config: &SearchConfig,
+) -> Comparison {
+    let breakdown = |loc: PieceLocation| {
+        let mut after = *game;
+        let info = after.advance(queue[0], loc);
+        eval.breakdown(game, &after, &info)
+    };
+    Comparison {
+        human: played,
+        human_terms: breakdown(played),
+        bot: search(game, queue, eval, config)
+            .ok()
+            .map(|bot| (bot, breakdown(bot))),
+    }
+}
@@ -1,17 +1,181 @@
 use std::collections::BinaryHeap;
+use std::fmt;
 
 use ordered_float::NotNan;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
 
 use crate::data::*;
 use crate::eval::Eval;
-use crate::movegen::movegen;
+use crate::movegen::{movegen_piece_in, RotationSystem};
+use crate::movegen_cache::{CacheStats, MovegenCache};
 
-#[derive(Clone, Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchError {
+    // there's no legal placement for the current piece (or the hold piece)
+    NoMoves,
+    // the current piece can't spawn
+    ToppedOut,
+    // there's no current piece to place
+    EmptyQueue,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::NoMoves => write!(f, "no legal placements"),
+            SearchError::ToppedOut => write!(f, "board is topped out"),
+            SearchError::EmptyQueue => write!(f, "queue is empty"),
+        }
+    }
+}
+
+impl std::error::Error for SearchError {}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SearchConfig {
+    // how many pieces deep to search, including the current piece
+    pub depth: usize,
+    // how many nodes are kept in the beam after each layer
+    pub width: usize,
+    // how many pieces after the current one the search is allowed to see
+    pub preview: usize,
+    // nodes whose stack goes above this height get pruned
+    pub prune_height: u8,
+    pub allow_hold: bool,
+    pub allow_180: bool,
+    // kicks the game the search is playing for uses
+    pub rotation_system: RotationSystem,
+    // seed for the eval noise, None means a fresh seed every search
+    pub seed: Option<u64>,
+    // if set, at most this many nodes descending from the same first move survive each layer,
+    // so the beam can't collapse onto one or two lines. 0 would keep nothing, validate turns it
+    // away
+    pub root_quota: Option<usize>,
+    // placement rewards get multiplied by discount^n, where n is how many pieces deep they are
+    pub discount: f32,
+    // if set, movegen results for this many board and piece pairs are kept around for the rest
+    // of the search
+    pub movegen_cache: Option<usize>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            depth: 7,
+            width: 30000,
+            preview: 6,
+            prune_height: 16,
+            allow_hold: true,
+            allow_180: true,
+            rotation_system: RotationSystem::SrsPlus,
+            seed: None,
+            root_quota: None,
+            discount: 1.0,
+            movegen_cache: None,
+        }
+    }
+}
+
+impl SearchConfig {
+    // settings a search can't do anything useful with
+    pub fn validate(&self) -> Result<(), ParseError> {
+        if self.root_quota == Some(0) {
+            return Err(ParseError(
+                "root quota has to be at least 1, leave it out for no quota".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn rng(&self) -> StdRng {
+        match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_rng(&mut rand::rng()),
+        }
+    }
+}
+
+// every placement the config allows for the next piece, including the hold piece
+pub fn candidates(game: &Game, next: Piece, config: &SearchConfig) -> Vec<PieceLocation> {
+    let system = config.rotation_system;
+    let mut locs = movegen_piece_in(&game.board, next, system, config.allow_180);
+    if config.allow_hold {
+        locs.append(&mut movegen_piece_in(
+            &game.board,
+            game.hold(),
+            system,
+            config.allow_180,
+        ));
+    }
+    locs
+}
+
+#[derive(Clone, Copy, Debug)]
 pub struct Node {
+    // reward + value
     pub score: NotNan<f32>,
+    // discounted sum of the placement rewards along the path
+    pub reward: f32,
+    // static value of the board at this node, discounted the same way as the last reward
+    pub value: f32,
     pub id: usize,
     pub game: Game,
-    pub moves: im::Vector<(PieceLocation, PlacementInfo)>,
+    // index of the previous placement in the arena, NO_PARENT if this is a first move
+    pub parent: u32,
+    pub loc: PieceLocation,
+    pub info: PlacementInfo,
+}
+
+pub const NO_PARENT: u32 = u32::MAX;
+
+// one placement along a path. nodes only get pushed here once they survive a layer and are
+// expanded, so the arena stays around width * depth entries instead of every evaluated child
+#[derive(Clone, Copy, Debug)]
+struct Step {
+    parent: u32,
+    loc: PieceLocation,
+    info: PlacementInfo,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+#[serde(default)]
+pub struct SearchStats {
+    // number of placements that got evaluated
+    pub nodes: usize,
+    // how many pieces deep the beam actually got
+    pub depth: usize,
+    // all zero without a movegen cache
+    pub cache: CacheStats,
+}
+
+#[derive(Debug)]
+pub struct SearchResults {
+    pub nodes: BinaryHeap<Node>,
+    pub stats: SearchStats,
+    arena: Vec<Step>,
+}
+
+impl SearchResults {
+    pub fn best(&self) -> Option<&Node> {
+        // the heap is ordered worst first, so the "smallest" node has the highest score
+        self.nodes.iter().min()
+    }
+
+    // rebuild every placement leading up to (and including) the given node
+    pub fn path(&self, node: &Node) -> Vec<(PieceLocation, PlacementInfo)> {
+        let mut path = vec![(node.loc, node.info)];
+        let mut parent = node.parent;
+        while parent != NO_PARENT {
+            let step = self.arena[parent as usize];
+            path.push((step.loc, step.info));
+            parent = step.parent;
+        }
+        path.reverse();
+        path
+    }
 }
 
 impl PartialEq for Node {
@@ -39,88 +203,151 @@ impl Ord for Node {
 pub fn search_results(
     root: &Game,
     search_loc: &[PieceLocation],
-    queue: Vec<Piece>,
+    queue: &[Piece],
     eval: &Eval,
-    depth: usize,
-    width: usize,
-) -> BinaryHeap<Node> {
+    config: &SearchConfig,
+) -> Result<SearchResults, SearchError> {
+    let Some(&current) = queue.first() else {
+        return Err(SearchError::EmptyQueue);
+    };
+    if search_loc.is_empty() {
+        return Err(SearchError::NoMoves);
+    }
+    let width = config.width;
+    let depth = config.depth.min(config.preview + 1);
+    let mut rng = config.rng();
+    let mut stats = SearchStats::default();
+    let mut arena: Vec<Step> = Vec::with_capacity(width * depth);
     let mut heap: BinaryHeap<Node> = BinaryHeap::with_capacity(width + 1);
     // for each piece placement available for the next piece, insert into heap if the score is high
     // enough to justify insertion
     for (id, &loc) in search_loc.iter().enumerate() {
-        let mut game = root.clone();
-        let placement_info = game.advance(queue[0], loc);
-        let score = eval.eval(root, &game, &placement_info);
+        let mut game = *root;
+        let info = game.advance(current, loc);
+        let reward = eval.reward(root, &game, &info);
+        let value = eval.value(&game, &mut rng);
+        stats.nodes += 1;
         insert_if_better(
             &mut heap,
             Node {
                 game,
                 id,
-                score: NotNan::new(score).unwrap(),
-                moves: im::vector![(loc, placement_info)],
+                score: NotNan::new(reward + value).unwrap(),
+                reward,
+                value,
+                parent: NO_PARENT,
+                loc,
+                info,
             },
             width,
         );
     }
+    stats.depth = 1;
     let mut next_heap: BinaryHeap<Node> = BinaryHeap::with_capacity(width + 1);
+    // with a root quota, children get bucketed by first move before competing for the beam
+    let quota = config.root_quota.map(|q| q.min(width));
+    let mut per_root: Vec<BinaryHeap<Node>> = match quota {
+        Some(_) => vec![BinaryHeap::new(); search_loc.len()],
+        None => vec![],
+    };
+    let mut cache = config
+        .movegen_cache
+        .map(|capacity| MovegenCache::new(capacity, config.rotation_system, config.allow_180));
+    let mut discount = 1.0;
     // then, for every other piece in the queue
     for next in queue.iter().take(depth).skip(1) {
+        discount *= config.discount;
         // for each placement based on the first piece
         for node in &heap {
+            let parent = arena.len() as u32;
+            arena.push(Step {
+                parent: node.parent,
+                loc: node.loc,
+                info: node.info,
+            });
             // find subsequent placements, and for each
-            for loc in movegen(&node.game, *next) {
-                let mut game = node.game.clone();
-                let placement_info = game.advance(*next, loc);
-                if game
-                    .board
-                    .cols
-                    .into_iter()
-                    .map(Column::height)
-                    .max()
-                    .unwrap()
-                    > 16
-                {
+            let locs = match &mut cache {
+                Some(cache) => cache.candidates(&node.game, *next, config.allow_hold),
+                None => candidates(&node.game, *next, config),
+            };
+            for loc in locs {
+                let mut game = node.game;
+                let info = game.advance(*next, loc);
+                if game.board.max_height() > config.prune_height {
                     continue;
                 }
-                let score = eval.eval(root, &game, &placement_info);
-                let mut moves = node.moves.clone();
-                moves.push_back((loc, placement_info));
-                insert_if_better(
-                    &mut next_heap,
-                    Node {
-                        game,
-                        id: node.id,
-                        score: NotNan::new(score).unwrap(),
-                        moves,
-                    },
-                    width,
-                );
+                let reward = node.reward + discount * eval.reward(&node.game, &game, &info);
+                let value = discount * eval.value(&game, &mut rng);
+                stats.nodes += 1;
+                let child = Node {
+                    game,
+                    id: node.id,
+                    score: NotNan::new(reward + value).unwrap(),
+                    reward,
+                    value,
+                    parent,
+                    loc,
+                    info,
+                };
+                match quota {
+                    Some(quota) => insert_if_better(&mut per_root[node.id], child, quota),
+                    None => insert_if_better(&mut next_heap, child, width),
+                }
+            }
+        }
+        for bucket in &mut per_root {
+            for child in bucket.drain() {
+                insert_if_better(&mut next_heap, child, width);
             }
         }
         if next_heap.is_empty() {
             break;
         }
+        stats.depth += 1;
         heap.clear();
         std::mem::swap(&mut heap, &mut next_heap);
     }
+    if let Some(cache) = cache {
+        stats.cache = cache.stats;
+    }
 
-    heap
+    Ok(SearchResults {
+        nodes: heap,
+        stats,
+        arena,
+    })
 }
 
 // why are there two heaps? How does info not get left behind in one heap or the other?
 // we are clearing the old one before we swap, then building placements ontop of it
 pub fn search(
     root: &Game,
-    queue: Vec<Piece>,
+    queue: &[Piece],
+    eval: &Eval,
+    config: &SearchConfig,
+) -> Result<PieceLocation, SearchError> {
+    search_with_stats(root, queue, eval, config).map(|(loc, _)| loc)
+}
+
+// same as search, but also says how much work the search did
+pub fn search_with_stats(
+    root: &Game,
+    queue: &[Piece],
     eval: &Eval,
-    depth: usize,
-    width: usize,
-) -> PieceLocation {
+    config: &SearchConfig,
+) -> Result<(PieceLocation, SearchStats), SearchError> {
+    let Some(&current) = queue.first() else {
+        return Err(SearchError::EmptyQueue);
+    };
+    if root.board.topped_out(current) {
+        return Err(SearchError::ToppedOut);
+    }
     // we get the locations for the next piece in the queue
-    let search_loc = movegen(root, queue[0]);
-    let heap = search_results(root, &search_loc, queue, eval, depth, width);
+    let search_loc = candidates(root, current, config);
+    let results = search_results(root, &search_loc, queue, eval, config)?;
 
-    search_loc[heap.into_iter().min().unwrap().id]
+    // the first layer always has at least one node since search_loc isn't empty
+    Ok((search_loc[results.best().unwrap().id], results.stats))
 }
 
 fn insert_if_better(heap: &mut BinaryHeap<Node>, node: Node, width: usize) {
@@ -133,3 +360,11 @@ fn insert_if_better(heap: &mut BinaryHeap<Node>, node: Node, width: usize) {
         }
     }
 }
+
+// the position `tetrizz bench` and benches/beam_search.rs time the search on, a ragged stack with
+// a two wide well
+pub fn bench_game(hold: Piece) -> Game {
+    let mut game = Game::new(Some(hold));
+    game.board = Board::new([2047, 1023, 511, 0, 0, 340, 8062, 16383, 5119, 2047].map(Column));
+    game
+}
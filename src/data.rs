@@ -1,8 +1,13 @@
 // use std::ops::{BitAnd, BitOr};
 
 use rand::prelude::IndexedRandom;
+use rand::seq::SliceRandom;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
+use std::fmt;
+use std::str::FromStr;
+
 #[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub enum Piece {
     I,
@@ -32,12 +37,80 @@ pub struct PieceLocation {
     pub possible_line_clear: bool,
 }
 
-#[derive(Debug, Copy, Clone)]
+// pieces spawn with their origin at (SPAWN_X, SPAWN_Y), just above the 20 visible rows
+pub const SPAWN_X: i8 = 4;
+pub const SPAWN_Y: i8 = 20;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(from = "Cells")]
 pub struct Board {
-    pub cols: [Column; 10],
+    // only changed through Board's methods, so the hash can't go stale
+    cols: [Column; 10],
+    // zobrist hash of the filled cells, the xor of CELL_KEYS for each of them
+    #[serde(skip)]
+    hash: u64,
+}
+
+// what a board is saved as, the hash gets worked out again on load
+#[derive(Deserialize)]
+struct Cells {
+    cols: [Column; 10],
+}
+
+impl From<Cells> for Board {
+    fn from(cells: Cells) -> Self {
+        Board::new(cells.cols)
+    }
+}
+
+// splitmix64, so zobrist keys come out the same on every run and machine
+const fn mix(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9e3779b97f4a7c15);
+    let x = (x ^ x >> 30).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ x >> 27).wrapping_mul(0x94d049bb133111eb);
+    x ^ x >> 31
+}
+
+const CELL_KEYS: [[u64; 64]; 10] = {
+    let mut keys = [[0; 64]; 10];
+    let mut i = 0;
+    while i < 640 {
+        keys[i / 64][i % 64] = mix(i as u64);
+        i += 1;
+    }
+    keys
+};
+
+const HOLD_KEYS: [u64; 7] = {
+    let mut keys = [0; 7];
+    let mut i = 0;
+    while i < 7 {
+        keys[i] = mix(1000 + i as u64);
+        i += 1;
+    }
+    keys
+};
+
+// b2b and combo have no upper bound, so their keys come straight from mix instead of a table
+fn b2b_key(b2b: u64) -> u64 {
+    mix(b2b.wrapping_add(1 << 62))
+}
+
+fn combo_key(combo: u32) -> u64 {
+    mix(combo as u64 + (1 << 63))
+}
+
+// the keys of the given cells in column x xored together
+fn cell_keys(x: usize, mut cells: u64) -> u64 {
+    let mut hash = 0;
+    while cells != 0 {
+        hash ^= CELL_KEYS[x][cells.trailing_zeros() as usize];
+        cells &= cells - 1;
+    }
+    hash
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Column(pub u64);
 
 impl Column {
@@ -55,22 +128,45 @@ impl Column {
             lines >>= 1;
         }
     }
+
+    // puts back full rows that clear took out, lines numbered as they were before the clear
+    fn unclear(&mut self, mut lines: u64) {
+        while lines != 0 {
+            let i = lines.trailing_zeros();
+            let mask = (1 << i) - 1;
+            self.0 = self.0 & mask | (self.0 & !mask) << 1 | 1 << i;
+            lines &= lines - 1;
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct Game {
     pub board: Board,
-    pub hold: Piece,
-    pub b2b: u64,
+    // hold, b2b and combo are in the hash, so they only change through the setters and advance
+    hold: Piece,
+    b2b: u64,
     pub b2b_deficit: u32,
+    // number of consecutive line clears, 0 after a placement that clears nothing
+    combo: u32,
+    // zobrist hash of hold, b2b and combo, the board keeps its own
+    state: u64,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
 pub struct PlacementInfo {
     pub spin: bool,
     pub lines_cleared: u32,
+    // rows that got cleared, as they were numbered before the clear
+    pub line_mask: u64,
+    // lines of garbage sent, only filled in by Game::advance since it depends on b2b and combo
+    pub attack: u32,
 }
 
+// extra attack for the nth clear in a row (capped at the last entry)
+const COMBO_ATTACK: [u32; 13] = [0, 0, 1, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const PERFECT_CLEAR_ATTACK: u32 = 10;
+
 impl Rotation {
     pub const fn rotate_block(&self, (x, y): (i8, i8)) -> (i8, i8) {
         match self {
@@ -130,6 +226,17 @@ impl Piece {
             Piece::T => [(-1, 0), (0, 0), (1, 0), (0, 1)],
         }
     }
+
+    pub const fn spawn(&self) -> PieceLocation {
+        PieceLocation {
+            piece: *self,
+            rotation: Rotation::North,
+            spun: false,
+            x: SPAWN_X,
+            y: SPAWN_Y,
+            possible_line_clear: false,
+        }
+    }
 }
 
 macro_rules! lutify {
@@ -180,10 +287,73 @@ impl PieceLocation {
 }
 
 impl Board {
+    pub fn new(cols: [Column; 10]) -> Self {
+        let mut board = Self { cols, hash: 0 };
+        board.rehash();
+        board
+    }
+
+    #[inline]
+    pub fn cols(&self) -> &[Column; 10] {
+        &self.cols
+    }
+
+    pub fn set_cols(&mut self, cols: [Column; 10]) {
+        *self = Self::new(cols);
+    }
+
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    // the hash from scratch. everything that changes the board keeps it up to date, this is for
+    // checking that it did
+    pub fn rehash(&mut self) {
+        self.hash = 0;
+        for (x, c) in self.cols.iter().enumerate() {
+            self.hash ^= cell_keys(x, c.0);
+        }
+    }
+
+    #[inline]
+    pub fn max_height(&self) -> u8 {
+        self.cols.iter().map(|&c| c.height()).max().unwrap()
+    }
+
+    // true if any block of loc is outside the board or overlaps a filled cell
+    pub fn collides(&self, loc: PieceLocation) -> bool {
+        loc.blocks().iter().any(|&(x, y)| {
+            !(0..10).contains(&x) || !(0..64).contains(&y) || self.cols[x as usize].0 & 1 << y != 0
+        })
+    }
+
+    // where loc ends up if it falls straight down from where it is
+    pub fn hard_drop(&self, loc: PieceLocation) -> PieceLocation {
+        let mut dropped = loc;
+        loop {
+            let lower = PieceLocation {
+                y: dropped.y - 1,
+                ..dropped
+            };
+            if self.collides(lower) {
+                return dropped;
+            }
+            dropped = lower;
+        }
+    }
+
+    // the game is over once the next piece can't spawn
+    pub fn topped_out(&self, next: Piece) -> bool {
+        self.collides(next.spawn())
+    }
+
     pub fn place(&mut self, loc: PieceLocation) -> PlacementInfo {
         let spin = loc.spun;
         for &(x, y) in &loc.blocks() {
-            self.cols[x as usize].0 |= 1 << y;
+            let col = &mut self.cols[x as usize].0;
+            // a cell that was already filled stays filled, so its key stays in
+            self.hash ^= CELL_KEYS[x as usize][y as usize] & (*col >> y & 1).wrapping_sub(1);
+            *col |= 1 << y;
         }
         let line_mask = match loc.possible_line_clear {
             true => self.remove_lines(),
@@ -192,30 +362,158 @@ impl Board {
         PlacementInfo {
             spin,
             lines_cleared: line_mask.count_ones(),
+            line_mask,
+            attack: 0,
         }
     }
 
+    // place, but a piece that's off the board or on filled cells is turned away instead of
+    // corrupting the board or panicking
+    pub fn try_place(&mut self, loc: PieceLocation) -> Result<PlacementInfo, PlaceError> {
+        for (x, y) in loc.blocks() {
+            if !(0..10).contains(&x) || !(0..64).contains(&y) {
+                return Err(PlaceError::OutOfBounds);
+            }
+            if self.cols[x as usize].0 & 1 << y != 0 {
+                return Err(PlaceError::Overlaps);
+            }
+        }
+        Ok(self.place(loc))
+    }
+
     pub fn remove_lines(&mut self) -> u64 {
         let lines = self.cols.iter().fold(!0, |a, b| a & b.0);
-        for c in &mut self.cols {
+        if lines == 0 {
+            return 0;
+        }
+        // only the cells that changed get swapped in the hash, which is none of the ones below the
+        // lowest cleared line
+        for (x, c) in self.cols.iter_mut().enumerate() {
+            let before = c.0;
             c.clear(lines);
+            self.hash ^= cell_keys(x, before ^ c.0);
         }
         lines
     }
+
+    // push the whole board up and fill the bottom rows with garbage, leaving a hole in one column
+    pub fn add_garbage(&mut self, lines: u32, hole: usize) {
+        let lines = lines.min(63);
+        for (x, c) in self.cols.iter_mut().enumerate() {
+            let before = c.0;
+            c.0 <<= lines;
+            if x != hole {
+                c.0 |= (1 << lines) - 1;
+            }
+            self.hash ^= cell_keys(x, before ^ c.0);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cols.iter().all(|c| c.0 == 0)
+    }
+}
+
+// which piece filled each cell. the bitboard doesn't keep this and every search node copies the
+// board, so it lives next to it instead of in it (see ColoredBoard). rows go bottom up and only as
+// high as anything got filled, garbage and anything else that wasn't placed as a piece is None
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct Colors {
+    pub rows: Vec<[Option<Piece>; 10]>,
+}
+
+impl Colors {
+    pub fn get(&self, x: usize, y: usize) -> Option<Piece> {
+        self.rows.get(y).and_then(|row| row[x])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, piece: Option<Piece>) {
+        if self.rows.len() <= y {
+            self.rows.resize(y + 1, [None; 10]);
+        }
+        self.rows[y][x] = piece;
+    }
+
+    // fill in loc, then drop the rows in line_mask, the same way Board::place does
+    pub fn place(&mut self, loc: PieceLocation, line_mask: u64) {
+        for (x, y) in loc.blocks() {
+            self.set(x as usize, y as usize, Some(loc.piece));
+        }
+        self.remove_lines(line_mask);
+    }
+
+    pub fn remove_lines(&mut self, line_mask: u64) {
+        for y in (0..self.rows.len().min(64)).rev() {
+            if line_mask & 1 << y != 0 {
+                self.rows.remove(y);
+            }
+        }
+    }
+
+    pub fn add_garbage(&mut self, lines: u32) {
+        let lines = lines.min(63) as usize;
+        self.rows
+            .splice(0..0, std::iter::repeat_n([None; 10], lines));
+    }
+}
+
+// a board that also remembers which piece filled each cell, for rendering, fumen export and replay
+// viewers. the search and eval only ever see the plain Board
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ColoredBoard {
+    pub board: Board,
+    pub colors: Colors,
+}
+
+impl ColoredBoard {
+    pub fn new(board: Board) -> Self {
+        Self {
+            board,
+            colors: Colors::default(),
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Option<Piece> {
+        self.colors.get(x, y)
+    }
+
+    pub fn place(&mut self, loc: PieceLocation) -> PlacementInfo {
+        let info = self.board.place(loc);
+        self.colors.place(loc, info.line_mask);
+        info
+    }
+
+    pub fn remove_lines(&mut self) -> u64 {
+        let lines = self.board.remove_lines();
+        self.colors.remove_lines(lines);
+        lines
+    }
+
+    pub fn add_garbage(&mut self, lines: u32, hole: usize) {
+        self.board.add_garbage(lines, hole);
+        self.colors.add_garbage(lines);
+    }
+}
+
+impl From<Board> for ColoredBoard {
+    fn from(board: Board) -> Self {
+        Self::new(board)
+    }
 }
 
 impl Game {
     pub fn new(p: Option<Piece>) -> Self {
         let mut game = Self {
-            board: Board {
-                cols: [Column(0); 10],
-            },
+            board: Board::new([Column(0); 10]),
             hold: Piece::Z, // placeholder
             b2b: 0,
             b2b_deficit: 0,
+            combo: 0,
+            state: 0,
         };
-        if p.is_some() {
-            game.hold = p.unwrap();
+        if let Some(p) = p {
+            game.hold = p;
+            game.rehash();
             return game;
         }
         let mut rng = rand::rng();
@@ -231,22 +529,313 @@ impl Game {
         .choose(&mut rng)
         .copied()
         .unwrap();
+        game.rehash();
         game
     }
 
+    // zobrist hash of the board, hold, b2b and combo
+    pub fn hash(&self) -> u64 {
+        self.board.hash() ^ self.state
+    }
+
+    #[inline]
+    pub fn hold(&self) -> Piece {
+        self.hold
+    }
+
+    #[inline]
+    pub fn b2b(&self) -> u64 {
+        self.b2b
+    }
+
+    #[inline]
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    pub fn set_hold(&mut self, hold: Piece) {
+        self.state ^= HOLD_KEYS[self.hold as usize] ^ HOLD_KEYS[hold as usize];
+        self.hold = hold;
+    }
+
+    pub fn set_b2b(&mut self, b2b: u64) {
+        self.state ^= b2b_key(self.b2b) ^ b2b_key(b2b);
+        self.b2b = b2b;
+    }
+
+    pub fn set_combo(&mut self, combo: u32) {
+        self.state ^= combo_key(self.combo) ^ combo_key(combo);
+        self.combo = combo;
+    }
+
+    // the hash from scratch. everything that changes the game keeps it up to date, this is for
+    // checking that it did
+    pub fn rehash(&mut self) {
+        self.board.rehash();
+        self.state = self.state_hash();
+    }
+
+    fn state_hash(&self) -> u64 {
+        HOLD_KEYS[self.hold as usize] ^ b2b_key(self.b2b) ^ combo_key(self.combo)
+    }
+
     pub fn advance(&mut self, next: Piece, loc: PieceLocation) -> PlacementInfo {
+        let (hold, b2b, combo) = (self.hold, self.b2b, self.combo);
         if loc.piece != next {
             self.hold = next;
         }
-        let info = self.board.place(loc);
+        let mut info = self.board.place(loc);
         if info.lines_cleared > 0 {
-            if info.spin || info.lines_cleared == 4 {
+            let b2b_clear = info.spin || info.lines_cleared == 4;
+            self.combo += 1;
+            info.attack = attack(loc.piece, &info, b2b_clear && self.b2b > 0, self.combo)
+                + PERFECT_CLEAR_ATTACK * self.board.is_empty() as u32;
+            if b2b_clear {
                 self.b2b += 1;
                 self.b2b_deficit = 0;
             } else {
                 self.b2b = 0;
             }
+        } else {
+            self.combo = 0;
+        }
+        // only what changed gets swapped in the hash
+        if self.hold != hold {
+            self.state ^= HOLD_KEYS[hold as usize] ^ HOLD_KEYS[self.hold as usize];
+        }
+        if self.b2b != b2b {
+            self.state ^= b2b_key(b2b) ^ b2b_key(self.b2b);
+        }
+        if self.combo != combo {
+            self.state ^= combo_key(combo) ^ combo_key(self.combo);
         }
         info
     }
 }
+
+// everything Game::advance changes that can't be worked out from the game after it
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Undo {
+    pub next: Piece,
+    pub loc: PieceLocation,
+    pub line_mask: u64,
+    pub hold: Piece,
+    pub b2b: u64,
+    pub b2b_deficit: u32,
+    pub combo: u32,
+}
+
+impl Game {
+    // advance, also returning what it takes to go back
+    pub fn advance_undoable(&mut self, next: Piece, loc: PieceLocation) -> (PlacementInfo, Undo) {
+        let undo = Undo {
+            next,
+            loc,
+            line_mask: 0,
+            hold: self.hold,
+            b2b: self.b2b,
+            b2b_deficit: self.b2b_deficit,
+            combo: self.combo,
+        };
+        let info = self.advance(next, loc);
+        (
+            info,
+            Undo {
+                line_mask: info.line_mask,
+                ..undo
+            },
+        )
+    }
+
+    // takes back the move undo was made for, which has to be the last one
+    pub fn undo(&mut self, undo: &Undo) {
+        let board = &mut self.board;
+        for (x, c) in board.cols.iter_mut().enumerate() {
+            let before = c.0;
+            c.unclear(undo.line_mask);
+            board.hash ^= cell_keys(x, before ^ c.0);
+        }
+        for (x, y) in undo.loc.blocks() {
+            board.cols[x as usize].0 &= !(1 << y);
+            board.hash ^= CELL_KEYS[x as usize][y as usize];
+        }
+        self.set_hold(undo.hold);
+        self.set_b2b(undo.b2b);
+        self.b2b_deficit = undo.b2b_deficit;
+        self.set_combo(undo.combo);
+    }
+}
+
+// a game that can step back through its moves and forward again. making a new move after
+// stepping back drops the ones that were undone
+#[derive(Debug, Clone)]
+pub struct History {
+    pub game: Game,
+    undo: Vec<Undo>,
+    redo: Vec<Undo>,
+}
+
+impl History {
+    pub fn new(game: Game) -> Self {
+        Self {
+            game,
+            undo: vec![],
+            redo: vec![],
+        }
+    }
+
+    pub fn advance(&mut self, next: Piece, loc: PieceLocation) -> PlacementInfo {
+        self.redo.clear();
+        let (info, undo) = self.game.advance_undoable(next, loc);
+        self.undo.push(undo);
+        info
+    }
+
+    // the move that got taken back, None at the start
+    pub fn undo(&mut self) -> Option<&Undo> {
+        let undo = self.undo.pop()?;
+        self.game.undo(&undo);
+        self.redo.push(undo);
+        self.redo.last()
+    }
+
+    // plays the last undone move again, None if there's nothing to redo
+    pub fn redo(&mut self) -> Option<PlacementInfo> {
+        let undo = self.redo.pop()?;
+        let (info, undo) = self.game.advance_undoable(undo.next, undo.loc);
+        self.undo.push(undo);
+        Some(info)
+    }
+
+    // the moves that led to the current game, oldest first
+    pub fn moves(&self) -> &[Undo] {
+        &self.undo
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
+
+// guideline-ish attack table. t-spins send double, spins with other pieces count as minis
+fn attack(piece: Piece, info: &PlacementInfo, b2b_bonus: bool, combo: u32) -> u32 {
+    let lines = info.lines_cleared;
+    let base = match (info.spin, piece) {
+        (true, Piece::T) => 2 * lines,
+        (true, _) => lines - 1,
+        (false, _) if lines == 4 => 4,
+        (false, _) => lines - 1,
+    };
+    let combo = COMBO_ATTACK[(combo as usize - 1).min(COMBO_ATTACK.len() - 1)];
+    base + b2b_bonus as u32 + combo
+}
+
+// a queue of shuffled 7-bags, with the first piece split off to start in hold
+pub fn gen_queue(bags: u32, rng: &mut impl Rng) -> (Piece, Vec<Piece>) {
+    let mut queue = gen_bags(bags, rng);
+    (queue.remove(0), queue)
+}
+
+// shuffled 7-bags, whole, for topping up a queue that came from gen_queue
+pub fn gen_bags(bags: u32, rng: &mut impl Rng) -> Vec<Piece> {
+    let bag = [
+        Piece::I,
+        Piece::J,
+        Piece::L,
+        Piece::O,
+        Piece::S,
+        Piece::T,
+        Piece::Z,
+    ];
+    let mut queue: Vec<Piece> = vec![];
+    for _ in 0..bags {
+        let mut new_bag = bag.to_vec();
+        new_bag.shuffle(rng);
+        queue.extend(new_bag);
+    }
+    queue
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaceError {
+    // part of the piece is outside the board
+    OutOfBounds,
+    // part of the piece is on a filled cell
+    Overlaps,
+}
+
+impl fmt::Display for PlaceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlaceError::OutOfBounds => write!(f, "piece is outside the board"),
+            PlaceError::Overlaps => write!(f, "piece overlaps filled cells"),
+        }
+    }
+}
+
+impl std::error::Error for PlaceError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError(pub String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for Piece {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "I" => Ok(Piece::I),
+            "O" => Ok(Piece::O),
+            "T" => Ok(Piece::T),
+            "L" => Ok(Piece::L),
+            "J" => Ok(Piece::J),
+            "S" => Ok(Piece::S),
+            "Z" => Ok(Piece::Z),
+            _ => Err(ParseError(format!("unknown piece {s:?}"))),
+        }
+    }
+}
+
+// a queue written as piece letters, like "TSZLJ"
+pub fn parse_queue(s: &str) -> Result<Vec<Piece>, ParseError> {
+    s.chars()
+        .filter(|c| !c.is_whitespace() && *c != ',')
+        .map(|c| c.to_string().parse())
+        .collect()
+}
+
+// rows of 10 cells from the top of the board down, separated by newlines or '/'. '.', '_' and
+// '-' are empty cells, anything else is filled
+impl FromStr for Board {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<&str> = s
+            .split(['\n', '/'])
+            .map(str::trim)
+            .filter(|r| !r.is_empty())
+            .collect();
+        if rows.len() > 64 {
+            return Err(ParseError(format!("{} rows is too tall", rows.len())));
+        }
+        let mut cols = [Column(0); 10];
+        for (y, row) in rows.iter().rev().enumerate() {
+            if row.chars().count() != 10 {
+                return Err(ParseError(format!("row {row:?} isn't 10 cells wide")));
+            }
+            for (x, c) in row.chars().enumerate() {
+                if !matches!(c, '.' | '_' | '-') {
+                    cols[x].0 |= 1 << y;
+                }
+            }
+        }
+        Ok(Board::new(cols))
+    }
+}
@@ -1,6 +1,13 @@
 use crate::data::*;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+use std::fs;
+use std::io;
+use std::path::Path;
+
+// weight files are just this struct as json, one field per weight
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct Eval {
     max_height: f32,
     max_height_half: f32,
@@ -18,7 +25,30 @@ pub struct Eval {
     b2b_diff: f32,
 }
 
+// the best weights we've tuned so far
+impl Default for Eval {
+    fn default() -> Self {
+        Self::new(
+            -79.400375,
+            -55.564907,
+            -125.680145,
+            -170.41902,
+            10.167948,
+            -172.78625,
+            -478.7291,
+            86.84883,
+            368.89203,
+            272.57874,
+            28.938646,
+            -104.59018,
+            -496.8832,
+            458.29822,
+        )
+    }
+}
+
 impl Eval {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         max_height: f32,
         max_height_half: f32,
@@ -72,10 +102,49 @@ impl Eval {
         }
     }
 
+    pub fn weights(&self) -> [f32; 14] {
+        [
+            self.max_height,
+            self.max_height_half,
+            self.max_height_quarter,
+            self.total_holes,
+            self.coveredness,
+            self.row_transitions,
+            self.depth4,
+            self.dependencies,
+            self.i_dependencies,
+            self.spikes,
+            self.concavity,
+            self.b2b_clear,
+            self.b2b_deficit,
+            self.b2b_diff,
+        ]
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+    }
+
+    // reward for a single placement, going from prev to game. the search adds these up along the
+    // path, so things like b2b gained or broken only get counted on the move that did them
+    pub fn reward(&self, prev: &Game, game: &Game, info: &PlacementInfo) -> f32 {
+        let b2b_clear = info.spin && info.lines_cleared > 0;
+        // going from b2b x4 to 0 is a reward of -4
+        let b2b_diff = game.b2b() as f32 - prev.b2b() as f32;
+
+        self.b2b_clear * b2b_clear as u8 as f32 + self.b2b_diff * b2b_diff
+    }
+
+    // every weighted term of the value, in VALUE_TERMS order. they add up to the value without
+    // its noise
     // new eval, largely copied from cc2 :3 (sorry mk im still learning)
-    pub fn eval(&self, root: &Game, game: &Game, info: &PlacementInfo) -> f32 {
+    fn value_terms(&self, game: &Game) -> [f32; 12] {
         // height
-        let heights: [i32; 10] = game.board.cols.map(|c| c.height() as _);
+        let heights: [i32; 10] = game.board.cols().map(|c| c.height() as _);
 
         let max_height = *heights.iter().max().unwrap();
         let max_height_half = max_height.max(10) - 10;
@@ -84,7 +153,7 @@ impl Eval {
         // holes
         let total_holes = game
             .board
-            .cols
+            .cols()
             .iter()
             .map(|&c| {
                 let h = 64 - c.0.leading_zeros();
@@ -95,7 +164,7 @@ impl Eval {
 
         // coveredness
         let mut coveredness = 0;
-        for &c in &game.board.cols {
+        for &c in game.board.cols() {
             let h = 64 - c.0.leading_zeros();
             let under = (1 << h) - 1;
             let mut holes = !c.0 & under;
@@ -109,7 +178,7 @@ impl Eval {
         // row transitions
         let row_transitions = game
             .board
-            .cols
+            .cols()
             .windows(2)
             .map(|c| (c[0].0 ^ c[1].0).count_ones())
             .sum::<u32>();
@@ -117,14 +186,14 @@ impl Eval {
         // 4 line depth
         let (w_col, w_height) = game
             .board
-            .cols
+            .cols()
             .iter()
             .enumerate()
             .min_by_key(|&(_, h)| h)
             .unwrap();
         let almost_full_lines = game
             .board
-            .cols
+            .cols()
             .iter()
             .enumerate()
             .filter(|&(i, _)| i != w_col)
@@ -154,33 +223,68 @@ impl Eval {
         }
 
         // other stuff
-        let b2b_clear = info.spin && info.lines_cleared > 0;
         let b2b_deficit = game.b2b_deficit;
 
-        // remove this in the future
-        let b2b_diff = game.b2b.saturating_sub(root.b2b);
+        [
+            self.max_height * max_height as f32,
+            self.max_height_half * max_height_half as f32,
+            self.max_height_quarter * max_height_quarter as f32,
+            self.total_holes * total_holes as f32,
+            self.coveredness * coveredness as f32,
+            self.row_transitions * row_transitions as f32,
+            self.depth4 * depth4 as f32,
+            self.dependencies * dependencies as f32,
+            self.i_dependencies * i_dependencies as f32,
+            self.spikes * spikes as f32,
+            self.concavity * concavity as f32,
+            self.b2b_deficit * b2b_deficit as f32,
+        ]
+    }
 
-        // final part: add some rng so can encourage exploration
+    // static value of the board at the end of a path
+    pub fn value(&self, game: &Game, rng: &mut impl Rng) -> f32 {
+        let res: f32 = self.value_terms(game).iter().sum();
 
-        let res = self.max_height * max_height as f32
-            + self.max_height_half * max_height_half as f32
-            + self.max_height_quarter * max_height_quarter as f32
-            + self.total_holes * total_holes as f32
-            + self.coveredness * coveredness as f32
-            + self.row_transitions * row_transitions as f32
-            + self.depth4 * depth4 as f32
-            + self.dependencies * dependencies as f32
-            + self.i_dependencies * i_dependencies as f32
-            + self.spikes * spikes as f32
-            + self.concavity * concavity as f32
-            + self.b2b_clear * b2b_clear as u8 as f32
-            + self.b2b_deficit * b2b_deficit as f32
-            + self.b2b_diff * b2b_diff as f32;
-
-        let mut rng = rand::rng();
+        // final part: add some rng so can encourage exploration
         let noise = 0.03 * rng.random_range(-res.abs()..=res.abs());
 
         // higher is better
         res + noise
     }
 }
+
+const VALUE_TERMS: [&str; 12] = [
+    "max_height",
+    "max_height_half",
+    "max_height_quarter",
+    "total_holes",
+    "coveredness",
+    "row_transitions",
+    "depth4",
+    "dependencies",
+    "i_dependencies",
+    "spikes",
+    "concavity",
+    "b2b_deficit",
+];
+
+impl Eval {
+    // what a single placement scored and where it came from: the two reward terms, then every
+    // weighted term of the value of the board it left, without noise. they add up to the score
+    // a one piece search gives it
+    pub fn breakdown(
+        &self,
+        prev: &Game,
+        game: &Game,
+        info: &PlacementInfo,
+    ) -> Vec<(&'static str, f32)> {
+        let b2b_clear = info.spin && info.lines_cleared > 0;
+        let b2b_diff = game.b2b() as f32 - prev.b2b() as f32;
+        let mut terms = vec![
+            ("b2b_clear", self.b2b_clear * b2b_clear as u8 as f32),
+            ("b2b_diff", self.b2b_diff * b2b_diff),
+        ];
+        terms.extend(VALUE_TERMS.into_iter().zip(self.value_terms(game)));
+        terms
+    }
+}
@@ -0,0 +1,66 @@
+use crate::eval::Eval;
+use crate::simulate::{play, GameStats, SimConfig};
+
+use serde::{Deserialize, Serialize};
+
+// what a fitness run gets scored on. all of them are averaged over every game in the run
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    // attack sent divided by the pieces the game was meant to last, so dying early costs
+    AttackPerPiece,
+    // lines cleared before topping out or running out of pieces
+    LinesSurvived,
+    // number of clears that kept b2b going (spins and quads)
+    B2bCount,
+    // longest b2b chain times 250 / pieces, the same numbers the tuner used to go for
+    MaxB2b,
+    // garbage lines cleared per garbage line received, needs garbage to be turned on
+    DownstackEfficiency,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct FitnessConfig {
+    pub metric: Metric,
+    // which games get played. they're seeded, so every weight set sees the exact same games
+    #[serde(flatten)]
+    pub sim: SimConfig,
+}
+
+impl Default for FitnessConfig {
+    fn default() -> Self {
+        Self {
+            metric: Metric::MaxB2b,
+            sim: SimConfig::default(),
+        }
+    }
+}
+
+// `pieces` is how many pieces the game was meant to last
+pub fn score(stats: &GameStats, metric: Metric, pieces: usize) -> f32 {
+    match metric {
+        Metric::AttackPerPiece => stats.attack as f32 / pieces.max(1) as f32,
+        Metric::LinesSurvived => stats.lines as f32,
+        Metric::B2bCount => stats.b2b_clears as f32,
+        Metric::MaxB2b => 250.0 * stats.max_b2b as f32 / pieces.max(1) as f32,
+        Metric::DownstackEfficiency => {
+            stats.garbage_cleared as f32 / stats.garbage_received.max(1) as f32
+        }
+    }
+}
+
+pub fn evaluate(eval: &Eval, config: &FitnessConfig) -> Vec<GameStats> {
+    (0..config.sim.games as u64)
+        .map(|i| play(eval, &config.sim, config.sim.seed.wrapping_add(i)))
+        .collect()
+}
+
+// mean score over every game
+pub fn fitness(eval: &Eval, config: &FitnessConfig) -> f32 {
+    let stats = evaluate(eval, config);
+    stats
+        .iter()
+        .map(|s| score(s, config.metric, config.sim.pieces))
+        .sum::<f32>()
+        / stats.len().max(1) as f32
+}
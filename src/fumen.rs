@@ -0,0 +1,138 @@
+use crate::data::*;
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// fumen fields are 23 rows plus the garbage row under the floor, 10 cells each, top row first
+const FIELD_ROWS: usize = 24;
+const FIELD_CELLS: usize = FIELD_ROWS * 10;
+
+fn decode_char(c: u8) -> Result<u32, ParseError> {
+    BASE64
+        .iter()
+        .position(|&b| b == c)
+        .map(|i| i as u32)
+        .ok_or_else(|| ParseError(format!("{:?} isn't a fumen character", c as char)))
+}
+
+// fumen's numbering for what's in a cell. 0 is empty and 8 is garbage
+fn block(piece: Option<Piece>) -> u32 {
+    match piece {
+        Some(Piece::I) => 1,
+        Some(Piece::L) => 2,
+        Some(Piece::O) => 3,
+        Some(Piece::Z) => 4,
+        Some(Piece::T) => 5,
+        Some(Piece::J) => 6,
+        Some(Piece::S) => 7,
+        None => 8,
+    }
+}
+
+fn piece(block: u32) -> Option<Piece> {
+    match block {
+        1 => Some(Piece::I),
+        2 => Some(Piece::L),
+        3 => Some(Piece::O),
+        4 => Some(Piece::Z),
+        5 => Some(Piece::T),
+        6 => Some(Piece::J),
+        7 => Some(Piece::S),
+        _ => None,
+    }
+}
+
+// the board on the first page of a v115 fumen, like "v115@9gA8IeA8...". the garbage row under the
+// floor is dropped
+pub fn decode(fumen: &str) -> Result<Board, ParseError> {
+    decode_colored(fumen).map(|b| b.board)
+}
+
+// same as decode, keeping which piece each cell is colored as
+pub fn decode_colored(fumen: &str) -> Result<ColoredBoard, ParseError> {
+    let data = fumen
+        .trim()
+        .strip_prefix("v115@")
+        .or_else(|| fumen.trim().strip_prefix("m115@"))
+        .or_else(|| fumen.trim().strip_prefix("d115@"))
+        .ok_or_else(|| ParseError("only v115 fumens are supported".to_string()))?;
+    // long fumens get a '?' every 47 characters
+    let data: Vec<u8> = data.bytes().filter(|&b| b != b'?').collect();
+
+    let mut board = ColoredBoard::new(Board::new([Column(0); 10]));
+    let mut cols = [Column(0); 10];
+    let mut chars = data.chunks(2);
+    let mut cell = 0;
+    while cell < FIELD_CELLS {
+        let Some(&[a, b]) = chars.next() else {
+            return Err(ParseError("fumen field ends early".to_string()));
+        };
+        let value = decode_char(a)? + 64 * decode_char(b)?;
+        // the first page is a diff against an empty field, so 8 means empty, 9..16 is a block
+        let block = value / FIELD_CELLS as u32;
+        let run = (value % FIELD_CELLS as u32) as usize + 1;
+        if !(8..=16).contains(&block) || cell + run > FIELD_CELLS {
+            return Err(ParseError("fumen field is corrupt".to_string()));
+        }
+        if block != 8 {
+            for i in cell..cell + run {
+                let row = i / 10;
+                if row < FIELD_ROWS - 1 {
+                    let y = FIELD_ROWS - 2 - row;
+                    cols[i % 10].0 |= 1 << y;
+                    board.colors.set(i % 10, y, piece(block - 8));
+                }
+            }
+        }
+        cell += run;
+    }
+    board.board = Board::new(cols);
+    Ok(board)
+}
+
+// a single page v115 fumen of the board. cells above the 23 rows fumen has are cut off, and cells
+// without a color come out as garbage
+pub fn encode(board: &ColoredBoard) -> String {
+    let mut cells = [0; FIELD_CELLS];
+    for (i, cell) in cells.iter_mut().enumerate().take(FIELD_CELLS - 10) {
+        let (x, y) = (i % 10, FIELD_ROWS - 2 - i / 10);
+        if board.board.cols()[x].0 & 1 << y != 0 {
+            *cell = block(board.get(x, y));
+        }
+    }
+
+    let mut data = String::new();
+    let mut push = |value: u32, chars: usize| {
+        let mut value = value;
+        for _ in 0..chars {
+            data.push(BASE64[(value % 64) as usize] as char);
+            value /= 64;
+        }
+    };
+    let mut runs = vec![];
+    for &cell in &cells {
+        match runs.last_mut() {
+            Some((block, run)) if *block == cell => *run += 1,
+            _ => runs.push((cell, 1)),
+        }
+    }
+    for &(block, run) in &runs {
+        push((block + 8) * FIELD_CELLS as u32 + run - 1, 2);
+    }
+    // an empty field is followed by how many more empty pages come after it
+    if runs == [(0, FIELD_CELLS as u32)] {
+        push(0, 1);
+    }
+    // no piece on the page, just the colorize flag every first page has
+    push(4 * 32 * FIELD_CELLS as u32, 3);
+
+    // a '?' goes after the first 42 characters and then every 47, which is every 47 counting the
+    // "v115@"
+    let mut fumen = String::from("v115@");
+    for (i, c) in data.chars().enumerate() {
+        if i >= 42 && (i - 42) % 47 == 0 {
+            fumen.push('?');
+        }
+        fumen.push(c);
+    }
+    fumen
+}
@@ -1,72 +1,74 @@
-use crate::beam_search::*;
-use crate::data::*;
+use crate::data::ParseError;
 use crate::eval::Eval;
-
-use rand::seq::SliceRandom;
+use crate::fitness::{fitness, FitnessConfig};
 
 use rand::prelude::IteratorRandom;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use std::io::Write;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU32, Ordering};
 
-fn gen_queue(bags: u32) -> (Piece, Vec<Piece>) {
-    let mut rng = rand::rng();
-    let bag = [
-        Piece::I,
-        Piece::J,
-        Piece::L,
-        Piece::O,
-        Piece::S,
-        Piece::T,
-        Piece::Z,
-    ];
-    let mut queue: Vec<Piece> = vec![];
-    for _ in 0..bags {
-        let mut new_bag = bag.to_vec();
-        new_bag.shuffle(&mut rng);
-        queue.extend(new_bag);
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct GeneticConfig {
+    // number of agents kept after every generation
+    pub population: usize,
+    pub generations: usize,
+    // new agents made each generation by combining the two best of a tournament
+    pub crossovers: usize,
+    // new agents made each generation by nudging the best of a tournament
+    pub mutations: usize,
+    // how many random agents get picked for each tournament
+    pub tournament: usize,
+    // mutations add a uniform random value in -strength..strength to every weight
+    pub mutation_strength: f32,
+    // how agents get scored. every agent plays the same seeded games
+    pub fitness: FitnessConfig,
+}
+
+impl Default for GeneticConfig {
+    fn default() -> Self {
+        Self {
+            population: 250,
+            generations: 20,
+            crossovers: 70,
+            mutations: 30,
+            tournament: 50,
+            mutation_strength: 20.0,
+            fitness: FitnessConfig::default(),
+        }
     }
-    (queue.remove(0), queue)
 }
 
-pub fn eval_fitness(queue: Vec<Piece>, hold: Piece, weights: [f32; 14]) -> f32 {
-    const GAMES_PLAYED: usize = 4;
-    const MOVES_MADE: usize = 500;
-
-    let mut fitnesses: Vec<f32> = vec![];
-    for _ in 0..GAMES_PLAYED {
-        let mut test_queue = queue.clone();
-        let test_hold = hold;
-        let mut game = Game::new(Some(test_hold));
-        let eval = Eval::from(weights);
-        let mut max: u64 = 0;
-        for _ in 0..MOVES_MADE {
-            let loc = search(&game, test_queue.clone(), &eval, 15, 3000);
-            game.advance(test_queue[0], loc);
-            if loc.piece == game.hold {
-                game.hold = test_queue[0];
-            }
-            test_queue.remove(0);
-            if game
-                .board
-                .cols
-                .into_iter()
-                .map(Column::height)
-                .max()
-                .unwrap()
-                > 15
-            {
-                break;
-            }
-            if game.b2b > max {
-                max = game.b2b;
-            }
+impl GeneticConfig {
+    // settings the run can't go ahead with, like a tournament bigger than the population
+    pub fn validate(&self) -> Result<(), ParseError> {
+        let error = |e: String| Err(ParseError(e));
+        if self.population < 2 {
+            return error(format!(
+                "population has to be at least 2, not {}",
+                self.population
+            ));
         }
-        fitnesses.push(250.0 * max as f32 / MOVES_MADE as f32);
+        if !(2..=self.population).contains(&self.tournament) {
+            return error(format!(
+                "tournament has to be between 2 and the population ({}), not {}",
+                self.population, self.tournament
+            ));
+        }
+        if !(self.mutation_strength >= 0.0 && self.mutation_strength.is_finite()) {
+            return error(format!(
+                "mutation strength can't be {}",
+                self.mutation_strength
+            ));
+        }
+        self.fitness.sim.search.validate()
     }
-    fitnesses.iter().sum::<f32>() / GAMES_PLAYED as f32
 }
 
 pub fn normalized(weights: [f32; 14]) -> [f32; 14] {
@@ -74,15 +76,14 @@ pub fn normalized(weights: [f32; 14]) -> [f32; 14] {
     weights.map(|x| x / mag)
 }
 
-#[derive(Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Agent {
     pub weights: [f32; 14],
     pub fitness: f32,
 }
 
 impl Agent {
-    fn new_random() -> Self {
-        let mut rng = rand::rng();
+    fn new_random(rng: &mut impl Rng) -> Self {
         let mut arr = [0f32; 14];
         for x in &mut arr {
             *x = rng.random_range(-1.0..=1.0);
@@ -109,38 +110,112 @@ impl Agent {
     }
 }
 
-pub fn run_genetic_algo() {
-    const NUM_AGENTS: usize = 250;
-    const GENETIC_ITERATIONS: usize = 20;
-    const REPRODUCE: usize = 70;
-    const MUTATE: usize = 30;
-    const BATCH_POPULATION: usize = 50;
+// everything needed to pick a tuning run back up where it left off
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Checkpoint {
+    pub config: GeneticConfig,
+    // how many generations have been completed so far
+    pub generation: usize,
+    // the population going into the next generation, with the fitnesses they were last given
+    pub agents: Vec<Agent>,
+    pub best_agent: Agent,
+    // all of the tuner's randomness for a generation comes from a StdRng seeded with this, and
+    // the next seed gets drawn from it at the end, so this is the whole rng state
+    pub seed: u64,
+}
+
+impl Checkpoint {
+    pub fn new(config: GeneticConfig, seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let agents = (0..config.population)
+            .map(|_| Agent::new_random(&mut rng))
+            .collect();
+        Self {
+            config,
+            generation: 0,
+            agents,
+            best_agent: Agent::new_random(&mut rng),
+            seed: rng.random(),
+        }
+    }
+
+    pub fn path(dir: &Path, generation: usize) -> PathBuf {
+        dir.join(format!("gen-{generation:04}.json"))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        // write to a temp file and rename so a crash mid-write can't leave a broken checkpoint
+        let path = Self::path(dir, self.generation);
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string(self)?)?;
+        fs::rename(tmp, path)
+    }
 
-    let mut rng = rand::rng();
-    let mut agents: Vec<Agent> = (0..NUM_AGENTS).map(|_| Agent::new_random()).collect();
+    // the checkpoint with the highest generation in dir
+    pub fn latest(dir: &Path) -> io::Result<Self> {
+        let mut latest = None;
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name();
+            let generation = name
+                .to_str()
+                .and_then(|n| n.strip_prefix("gen-")?.strip_suffix(".json"))
+                .and_then(|n| n.parse::<usize>().ok());
+            if generation > latest {
+                latest = generation;
+            }
+        }
+        match latest {
+            Some(generation) => Self::load(Self::path(dir, generation)),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no checkpoints in {}", dir.display()),
+            )),
+        }
+    }
+}
 
-    let mut best_agent: Agent = Agent::new_random();
+// runs (or continues) the whole thing, writing the best weights so far to `out` and, if given, a
+// checkpoint to `checkpoints` after every generation. a config that doesn't validate is an
+// InvalidInput error
+pub fn run_genetic_algo(
+    mut state: Checkpoint,
+    out: &Path,
+    checkpoints: Option<&Path>,
+) -> io::Result<Agent> {
+    let config = state.config;
+    config
+        .validate()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let num_agents = config.population;
+    let generations = config.generations;
+    let tournament = config.tournament;
+    let strength = config.mutation_strength;
 
-    for n in 0..GENETIC_ITERATIONS {
-        println!("\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n🤩🤩🤩🤩🤩🤩🤩🤩\n🤩🤩🤩🤩🤩🤩🤩🤩\n🤩🤩🤩🤩🤩🤩🤩🤩\n🤩🤩🤩🤩🤩🤩🤩🤩\n\x1b[1mITERATION {}/{GENETIC_ITERATIONS}\x1b[0m", n + 1);
-        let (hold, queue) = gen_queue(200);
+    while state.generation < generations {
+        let n = state.generation;
+        let mut rng = StdRng::seed_from_u64(state.seed);
+        let agents = &mut state.agents;
+        let mut best_agent = state.best_agent.clone();
+        println!("\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n\n🤩🤩🤩🤩🤩🤩🤩🤩\n🤩🤩🤩🤩🤩🤩🤩🤩\n🤩🤩🤩🤩🤩🤩🤩🤩\n🤩🤩🤩🤩🤩🤩🤩🤩\n\x1b[1mITERATION {}/{generations}\x1b[0m", n + 1);
         let started = AtomicU32::new(0);
         let completed = AtomicU32::new(0);
         agents.par_iter_mut()
             .for_each(|agent| {
-                let start_prev = started.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| Some(x + 1)).unwrap();
-                unsafe {
-                    let scol = if start_prev != NUM_AGENTS as u32 { "\x1b[1;33m" } else { "\x1b[1;32m" };
-                    print!("   --- {scol}Started: {start_prev}/{NUM_AGENTS}\x1b[0m\t\t\x1b[1;33mCompleted: {}/{NUM_AGENTS}\x1b[0m\r", *completed.as_ptr());
-                    let _ = std::io::stdout().flush();
-                }
-                agent.fitness = eval_fitness(queue.clone(), hold, agent.weights);
-                let completed_prev = completed.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |x| Some(x + 1)).unwrap();
-                unsafe {
-                    let scol = if *started.as_ptr() != NUM_AGENTS as u32 { "\x1b[1;33m" } else { "\x1b[1;32m" };
-                    print!("   --- {scol}Started: {}/{NUM_AGENTS}\x1b[0m\t\t\x1b[1;33mCompleted: {completed_prev}/{NUM_AGENTS}\x1b[0m\r", *started.as_ptr());
-                    let _ = std::io::stdout().flush();
-                }
+                let start_prev = started.fetch_add(1, Ordering::Relaxed) + 1;
+                let scol = if start_prev != num_agents as u32 { "\x1b[1;33m" } else { "\x1b[1;32m" };
+                print!("   --- {scol}Started: {start_prev}/{num_agents}\x1b[0m\t\t\x1b[1;33mCompleted: {}/{num_agents}\x1b[0m\r", completed.load(Ordering::Relaxed));
+                let _ = std::io::stdout().flush();
+                agent.fitness = fitness(&Eval::from(agent.weights), &config.fitness);
+                let completed_prev = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                let started = started.load(Ordering::Relaxed);
+                let scol = if started != num_agents as u32 { "\x1b[1;33m" } else { "\x1b[1;32m" };
+                print!("   --- {scol}Started: {started}/{num_agents}\x1b[0m\t\t\x1b[1;33mCompleted: {completed_prev}/{num_agents}\x1b[0m\r");
+                let _ = std::io::stdout().flush();
             });
 
         best_agent = agents.iter().fold(best_agent, |a, b| {
@@ -150,9 +225,10 @@ pub fn run_genetic_algo() {
                 b.clone()
             }
         });
+        Eval::from(best_agent.weights).save(out)?;
 
-        for _ in 0..REPRODUCE {
-            let mut select_two_agents = agents.iter().choose_multiple(&mut rng, BATCH_POPULATION);
+        for _ in 0..config.crossovers {
+            let mut select_two_agents = agents.iter().choose_multiple(&mut rng, tournament);
             select_two_agents.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
             let new_agent = select_two_agents[0].combine(select_two_agents[1]);
             if let Some(agent) = new_agent {
@@ -160,21 +236,21 @@ pub fn run_genetic_algo() {
             }
         }
 
-        for _ in 0..MUTATE {
-            let mut select_two_agents = agents.iter().choose_multiple(&mut rng, BATCH_POPULATION);
+        for _ in 0..config.mutations {
+            let mut select_two_agents = agents.iter().choose_multiple(&mut rng, tournament);
             select_two_agents.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
             let mut new_agent = select_two_agents[0].clone();
             for weight in &mut new_agent.weights {
-                *weight += rng.random_range(-20.0..20.0);
+                *weight += rng.random_range(-strength..=strength);
             }
             agents.push(new_agent);
         }
         agents.sort_by(|a, b| b.fitness.partial_cmp(&a.fitness).unwrap());
-        agents = agents[0..NUM_AGENTS]
+        *agents = agents[0..num_agents]
             .iter()
             .map(|x| {
                 if x.fitness == 0.0 {
-                    Agent::new_random()
+                    Agent::new_random(&mut rng)
                 } else {
                     x.clone()
                 }
@@ -187,12 +263,23 @@ pub fn run_genetic_algo() {
             agents
                 .iter()
                 .cloned()
-                .fold(Agent::new_random(), |a, b| if a.fitness > b.fitness {
-                    a
-                } else {
-                    b
+                .fold(Agent::new_random(&mut rng), |a, b| {
+                    if a.fitness > b.fitness {
+                        a
+                    } else {
+                        b
+                    }
                 })
         );
         println!("\x1b[1mBest agent: \x1b[0m{best_agent:?}");
+
+        state.best_agent = best_agent;
+        state.generation += 1;
+        state.seed = rng.random();
+        if let Some(dir) = checkpoints {
+            state.save(dir)?;
+        }
     }
+
+    Ok(state.best_agent)
 }
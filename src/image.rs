@@ -0,0 +1,315 @@
+use crate::data::*;
+use crate::render::{Cell, View};
+use crate::replay::{Player, Replay};
+
+use std::fmt::Write;
+
+// guideline piece colors
+pub fn piece_color(piece: Piece) -> [u8; 3] {
+    match piece {
+        Piece::I => [0, 240, 240],
+        Piece::O => [240, 240, 0],
+        Piece::T => [160, 0, 240],
+        Piece::L => [240, 160, 0],
+        Piece::J => [0, 0, 240],
+        Piece::S => [0, 240, 0],
+        Piece::Z => [240, 0, 0],
+    }
+}
+
+const BACKGROUND: [u8; 3] = [24, 24, 24];
+const EMPTY: [u8; 3] = [40, 40, 40];
+const GARBAGE: [u8; 3] = [110, 110, 110];
+const OUTLINE: [u8; 3] = [255, 255, 255];
+const TEXT: [u8; 3] = [220, 220, 220];
+
+struct Rect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    color: [u8; 3],
+    alpha: f32,
+}
+
+struct Text {
+    x: f32,
+    y: f32,
+    size: f32,
+    text: String,
+}
+
+// everything in an image as plain shapes, so svg and png come out of the same layout
+struct Scene {
+    width: f32,
+    height: f32,
+    rects: Vec<Rect>,
+    texts: Vec<Text>,
+}
+
+impl Scene {
+    fn rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [u8; 3], alpha: f32) {
+        self.rects.push(Rect {
+            x,
+            y,
+            w,
+            h,
+            color,
+            alpha,
+        });
+    }
+
+    fn text(&mut self, x: f32, y: f32, size: f32, text: &str) {
+        self.texts.push(Text {
+            x,
+            y,
+            size,
+            text: text.to_string(),
+        });
+    }
+
+    // a piece in its spawn orientation with its top left corner at (x, y)
+    fn mini_piece(&mut self, piece: Piece, x: f32, y: f32, size: f32) {
+        for (bx, by) in piece.blocks() {
+            let cx = x + (bx + 1) as f32 * size;
+            let cy = y + (1 - by) as f32 * size;
+            self.rect(cx, cy, size - 1.0, size - 1.0, piece_color(piece), 1.0);
+        }
+    }
+
+    // draws the view with its top left corner at (x, y) and returns how much room it took
+    fn view(&mut self, view: &View, x: f32, y: f32, cell: f32) -> (f32, f32) {
+        let mut top = y;
+        if let Some(title) = &view.title {
+            self.text(x, top + cell * 0.8, cell * 0.7, title);
+            top += cell * 1.2;
+        }
+
+        let rows = view.rows() as usize;
+        self.rect(x, top, 10.0 * cell, rows as f32 * cell, BACKGROUND, 1.0);
+        for (i, cy) in (0..rows as i8).rev().enumerate() {
+            for cx in 0..10 {
+                let px = x + cx as f32 * cell;
+                let py = top + i as f32 * cell;
+                let inner = cell - 1.0;
+                match view.cell(cx, cy) {
+                    Cell::Empty => self.rect(px, py, inner, inner, EMPTY, 1.0),
+                    Cell::Filled(Some(piece)) => {
+                        self.rect(px, py, inner, inner, piece_color(piece), 1.0)
+                    }
+                    Cell::Filled(None) => self.rect(px, py, inner, inner, GARBAGE, 1.0),
+                    Cell::Piece(piece, _) => {
+                        self.rect(px, py, inner, inner, OUTLINE, 1.0);
+                        let border = (cell / 8.0).max(1.0);
+                        let fill = inner - 2.0 * border;
+                        self.rect(
+                            px + border,
+                            py + border,
+                            fill,
+                            fill,
+                            piece_color(piece),
+                            1.0,
+                        );
+                    }
+                    Cell::Ghost(piece) => self.rect(px, py, inner, inner, piece_color(piece), 0.35),
+                    Cell::Hint(piece) => self.rect(px, py, inner, inner, piece_color(piece), 0.7),
+                }
+            }
+        }
+        let board_height = top - y + rows as f32 * cell;
+
+        if view.hold.is_none() && view.queue.is_empty() && view.notes.is_empty() {
+            return (10.0 * cell, board_height);
+        }
+
+        // hold, then the queue, then the notes, down the right of the board
+        let mini = cell * 0.5;
+        let panel = x + 10.5 * cell;
+        let mut py = top;
+        if let Some(hold) = view.hold {
+            self.text(panel, py + mini, mini, "hold");
+            self.mini_piece(hold, panel, py + 1.5 * mini, mini);
+            py += 4.5 * mini;
+        }
+        if !view.queue.is_empty() {
+            self.text(panel, py + mini, mini, "next");
+            py += 1.5 * mini;
+            for &piece in view.queue {
+                self.mini_piece(piece, panel, py, mini);
+                py += 3.0 * mini;
+            }
+        }
+        for note in &view.notes {
+            py += 1.2 * mini;
+            self.text(panel, py, mini, note);
+        }
+        (12.5 * cell, board_height.max(py - y))
+    }
+}
+
+fn scene(views: &[View], cell: u32) -> Scene {
+    let cell = cell.max(4) as f32;
+    let margin = cell / 2.0;
+    let mut scene = Scene {
+        width: 0.0,
+        height: 0.0,
+        rects: vec![],
+        texts: vec![],
+    };
+    // the background has to be the first rect, but its size depends on everything after it
+    scene.rect(0.0, 0.0, 0.0, 0.0, BACKGROUND, 1.0);
+    let mut x = margin;
+    let mut height: f32 = 0.0;
+    for view in views {
+        let (w, h) = scene.view(view, x, margin, cell);
+        x += w + cell;
+        height = height.max(h);
+    }
+    scene.width = (x - cell + margin).max(2.0 * margin).ceil();
+    scene.height = (height + 2.0 * margin).ceil();
+    scene.rects[0].w = scene.width;
+    scene.rects[0].h = scene.height;
+    scene
+}
+
+// svg numbers don't need float noise like 40.800003
+fn num(x: f32) -> f32 {
+    (x * 100.0).round() / 100.0
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+// views next to each other as an svg document
+pub fn svg(views: &[View], cell: u32) -> String {
+    let scene = scene(views, cell);
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = scene.width,
+        h = scene.height
+    );
+    for r in &scene.rects {
+        let opacity = match r.alpha < 1.0 {
+            true => format!(r#" fill-opacity="{}""#, r.alpha),
+            false => String::new(),
+        };
+        let _ = writeln!(
+            out,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"{opacity}/>"#,
+            num(r.x),
+            num(r.y),
+            num(r.w),
+            num(r.h),
+            hex(r.color)
+        );
+    }
+    for t in &scene.texts {
+        let text = t
+            .text
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        let _ = writeln!(
+            out,
+            r#"<text x="{}" y="{}" font-family="monospace" font-size="{}" fill="{}">{text}</text>"#,
+            num(t.x),
+            num(t.y),
+            num(t.size),
+            hex(TEXT)
+        );
+    }
+    out.push_str("</svg>\n");
+    out
+}
+
+// whether the views have any text in them, which svg draws and png can't
+pub fn has_text(views: &[View]) -> bool {
+    !scene(views, 4).texts.is_empty()
+}
+
+// views next to each other as a png. there's no font to draw with, so titles, the hold and next
+// labels and notes are left out (see has_text), everything else matches the svg
+#[cfg(feature = "png")]
+pub fn png(views: &[View], cell: u32) -> std::io::Result<Vec<u8>> {
+    use tiny_skia::{Color, Paint, Pixmap, Rect as SkRect, Transform};
+
+    let scene = scene(views, cell);
+    let mut pixmap = Pixmap::new(scene.width as u32, scene.height as u32)
+        .ok_or_else(|| std::io::Error::other("image is too big"))?;
+    for r in &scene.rects {
+        let Some(rect) = SkRect::from_xywh(r.x, r.y, r.w, r.h) else {
+            continue;
+        };
+        let [red, green, blue] = r.color;
+        let mut paint = Paint::default();
+        paint.set_color(Color::from_rgba8(
+            red,
+            green,
+            blue,
+            (r.alpha * 255.0).round() as u8,
+        ));
+        pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+    }
+    pixmap.encode_png().map_err(std::io::Error::other)
+}
+
+struct Frame {
+    game: Game,
+    colors: Colors,
+    queue: Vec<Piece>,
+    // the move played from here, None at the end of the game
+    next: Option<PieceLocation>,
+    position: usize,
+}
+
+// one frame per placement, starting at move `start`: the board right before the move with the
+// piece it places outlined, and the hold and queue at that point
+pub struct Filmstrip {
+    frames: Vec<Frame>,
+}
+
+impl Filmstrip {
+    pub fn new(
+        replay: &Replay,
+        start: usize,
+        count: usize,
+        preview: usize,
+    ) -> Result<Self, ParseError> {
+        let mut player = Player::new(replay)?;
+        let mut frames = vec![];
+        for position in start..(start + count).min(player.len() + 1) {
+            player.seek(position);
+            let queue = player.queue();
+            frames.push(Frame {
+                game: *player.game(),
+                colors: player.colors().clone(),
+                queue: queue[..queue.len().min(preview + 1)].to_vec(),
+                next: player.next_move().map(|m| m.loc),
+                position,
+            });
+        }
+        Ok(Self { frames })
+    }
+
+    pub fn views(&self) -> Vec<View<'_>> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                let view = View::new(&frame.game.board)
+                    .colors(&frame.colors)
+                    .hold(frame.game.hold())
+                    .queue(&frame.queue)
+                    .height(20);
+                match frame.next {
+                    Some(loc) => view
+                        .piece(loc)
+                        .title(format!("move {}", frame.position + 1)),
+                    None => view.title(format!("after move {}", frame.position)),
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,394 @@
+use crate::analyze::{compare, Comparison};
+use crate::beam_search::SearchConfig;
+use crate::data::*;
+use crate::eval::Eval;
+use crate::movegen::{find_placement, movegen_piece_in, rotate, RotationSystem};
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportedMove {
+    // garbage that came up since the last piece locked, as (lines, hole column)
+    pub garbage: Vec<(u32, usize)>,
+    pub loc: PieceLocation,
+}
+
+// a game played in some other client, boiled down to what's needed to rebuild every position
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ImportedGame {
+    // every piece in the order the randomizer handed them out. the first one counts as held from
+    // the start, which gives the same choices as an empty hold (see Playfield)
+    pub pieces: Vec<Piece>,
+    pub moves: Vec<ImportedMove>,
+    pub rotation_system: RotationSystem,
+    pub allow_180: bool,
+}
+
+// why a move from an imported game can't be played
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Illegal {
+    // it's neither the current piece nor the hold piece
+    WrongPiece,
+    Blocked(PlaceError),
+    // the piece can't get there, or wouldn't stay there
+    Unreachable,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalMove {
+    // counting from 0
+    pub index: usize,
+    pub loc: PieceLocation,
+    pub reason: Illegal,
+}
+
+impl fmt::Display for IllegalMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let loc = self.loc;
+        write!(
+            f,
+            "move {} ({:?} {:?} x={} y={}): ",
+            self.index + 1,
+            loc.piece,
+            loc.rotation,
+            loc.x,
+            loc.y
+        )?;
+        match self.reason {
+            Illegal::WrongPiece => write!(f, "not the current or hold piece"),
+            Illegal::Blocked(e) => write!(f, "{e}"),
+            Illegal::Unreachable => write!(f, "the piece can't get there"),
+        }
+    }
+}
+
+impl std::error::Error for IllegalMove {}
+
+// the move as movegen would make it. the flags a saved game comes with aren't trusted, a wrong
+// possible_line_clear would leave full rows behind, so they come from movegen too. spun can only
+// stay set if movegen can spin the piece in, it knows nothing about what the player did last
+fn check(
+    game: &Game,
+    current: Piece,
+    loc: PieceLocation,
+    system: RotationSystem,
+    allow_180: bool,
+) -> Result<PieceLocation, Illegal> {
+    if loc.piece != current && loc.piece != game.hold() {
+        return Err(Illegal::WrongPiece);
+    }
+    let mut board = game.board;
+    board.try_place(loc).map_err(Illegal::Blocked)?;
+    let found = find_placement(&game.board, loc, system, allow_180).ok_or(Illegal::Unreachable)?;
+    Ok(PieceLocation {
+        spun: loc.spun && found.spun,
+        ..found
+    })
+}
+
+// the game right before a move, the queue starting with the current piece, and the move
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub game: Game,
+    pub queue: Vec<Piece>,
+    pub loc: PieceLocation,
+}
+
+impl ImportedGame {
+    // stops at the first move that couldn't have been played, with the game's own kicks and 180s
+    pub fn positions(&self, preview: usize) -> Result<Vec<Position>, IllegalMove> {
+        let mut game = Game::new(self.pieces.first().copied());
+        let mut positions = vec![];
+        for (i, m) in self.moves.iter().enumerate() {
+            for &(lines, hole) in &m.garbage {
+                game.board.add_garbage(lines, hole);
+            }
+            let queue = &self.pieces[(i + 1).min(self.pieces.len())..];
+            let queue = queue[..queue.len().min(preview + 1)].to_vec();
+            let Some(&current) = queue.first() else {
+                break;
+            };
+            let loc = check(&game, current, m.loc, self.rotation_system, self.allow_180).map_err(
+                |reason| IllegalMove {
+                    index: i,
+                    loc: m.loc,
+                    reason,
+                },
+            )?;
+            positions.push(Position { game, queue, loc });
+            game.advance(current, loc);
+        }
+        Ok(positions)
+    }
+}
+
+// every move of the game next to the bot's choice, searched one at a time as they're asked for.
+// the search plays by the game's kicks, and only gets 180s if the game had them
+pub fn review<'a>(
+    game: &ImportedGame,
+    eval: &'a Eval,
+    config: &SearchConfig,
+) -> Result<impl Iterator<Item = Comparison> + 'a, IllegalMove> {
+    let config = SearchConfig {
+        allow_180: config.allow_180 && game.allow_180,
+        rotation_system: game.rotation_system,
+        ..*config
+    };
+    Ok(game
+        .positions(config.preview)?
+        .into_iter()
+        .map(move |p| compare(&p.game, &p.queue, p.loc, eval, &config)))
+}
+
+// a placement that turns one board into another
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Inferred {
+    pub loc: PieceLocation,
+    pub info: PlacementInfo,
+    // the piece came out of hold, which now has the active piece in it
+    pub held: bool,
+}
+
+// every placement of the active piece, or of the hold piece, that leaves `after` once its lines
+// clear, for clients that only show the board. ones that don't need hold come first. holding into
+// an empty hold brings out a piece that isn't known here, so that's never tried, and garbage that
+// came up in between makes nothing match
+pub fn infer_placements(
+    before: &Board,
+    after: &Board,
+    active: Piece,
+    hold: Option<Piece>,
+    system: RotationSystem,
+    allow_180: bool,
+) -> Vec<Inferred> {
+    let mut pieces = vec![(active, false)];
+    if let Some(hold) = hold.filter(|&h| h != active) {
+        pieces.push((hold, true));
+    }
+    let mut found = vec![];
+    for (piece, held) in pieces {
+        for loc in movegen_piece_in(before, piece, system, allow_180) {
+            let mut board = *before;
+            let info = board.place(loc);
+            if board.cols() == after.cols() {
+                found.push(Inferred { loc, info, held });
+            }
+        }
+    }
+    found
+}
+
+// a board with a falling piece that gets pushed around by recorded inputs, for clients that save
+// inputs instead of placements. the client's hold starts empty, which lines up with Game by
+// treating the first piece as already held: either way the first piece can go now or after the
+// second, and from then on both hold the same piece
+pub struct Playfield {
+    pub game: Game,
+    pub pieces: Vec<Piece>,
+    // None between a lock and the next spawn, and once the game is over
+    pub active: Option<PieceLocation>,
+    pub moves: Vec<ImportedMove>,
+    pub rotation_system: RotationSystem,
+    pub allow_180: bool,
+    // the hold as the client shows it
+    hold: Option<Piece>,
+    // how many pieces have come out of `pieces`
+    spawned: usize,
+    held: bool,
+    // spins only count if the last thing the piece did was turn
+    rotated: bool,
+    garbage: Vec<(u32, usize)>,
+}
+
+impl Playfield {
+    // pieces can be added to as the game goes on, spawning with none left ends the game. the first
+    // piece is already out
+    pub fn new(pieces: Vec<Piece>, rotation_system: RotationSystem, allow_180: bool) -> Self {
+        let mut field = Self {
+            game: Game::new(pieces.first().copied()),
+            pieces,
+            active: None,
+            moves: vec![],
+            rotation_system,
+            allow_180,
+            hold: None,
+            spawned: 0,
+            held: false,
+            rotated: false,
+            garbage: vec![],
+        };
+        field.spawn();
+        field
+    }
+
+    pub fn spawned(&self) -> usize {
+        self.spawned
+    }
+
+    // the next piece, after a lock. ends the game if it doesn't fit
+    pub fn spawn(&mut self) {
+        self.active = self.pieces.get(self.spawned).map(|p| p.spawn());
+        self.spawned += 1;
+        self.take(self.active);
+    }
+
+    // ends the game if loc can't be there
+    fn take(&mut self, loc: Option<PieceLocation>) {
+        self.active = loc.filter(|&l| !self.game.board.collides(l));
+        self.held = false;
+        self.rotated = false;
+    }
+
+    fn moved(&mut self, dx: i8, dy: i8) -> bool {
+        let Some(loc) = self.active else {
+            return false;
+        };
+        let next = PieceLocation {
+            x: loc.x + dx,
+            y: loc.y + dy,
+            ..loc
+        };
+        if self.game.board.collides(next) {
+            return false;
+        }
+        self.active = Some(next);
+        self.rotated = false;
+        true
+    }
+
+    pub fn shift(&mut self, dx: i8) -> bool {
+        self.moved(dx, 0)
+    }
+
+    // all the way left or right
+    pub fn slide(&mut self, dx: i8) -> bool {
+        let mut moved = false;
+        while self.shift(dx) {
+            moved = true;
+        }
+        moved
+    }
+
+    pub fn drop(&mut self) -> bool {
+        self.moved(0, -1)
+    }
+
+    // all the way down without locking
+    pub fn sonic_drop(&mut self) -> bool {
+        let mut moved = false;
+        while self.drop() {
+            moved = true;
+        }
+        moved
+    }
+
+    pub fn grounded(&self) -> bool {
+        self.active.is_some_and(|loc| {
+            self.game.board.collides(PieceLocation {
+                y: loc.y - 1,
+                ..loc
+            })
+        })
+    }
+
+    pub fn rotate(&mut self, to: Rotation) -> bool {
+        let Some(loc) = self.active else {
+            return false;
+        };
+        if !self.allow_180 && to == loc.rotation.rotate_180() {
+            return false;
+        }
+        // clients let the o turn without moving, and its kicks are all (0, 0), so going through
+        // them would shift its cells
+        if loc.piece == Piece::O {
+            self.rotated = true;
+            return true;
+        }
+        match rotate(&self.game.board, loc, to, self.rotation_system) {
+            Some(next) => {
+                self.active = Some(next);
+                self.rotated = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn hold(&mut self) -> bool {
+        let Some(loc) = self.active else {
+            return false;
+        };
+        if self.held {
+            return false;
+        }
+        match self.hold.replace(loc.piece) {
+            Some(piece) => self.take(Some(piece.spawn())),
+            None => self.spawn(),
+        }
+        self.held = true;
+        true
+    }
+
+    // garbage coming up under the board, pushing the falling piece up with it if it has to
+    pub fn add_garbage(&mut self, lines: u32, hole: usize) {
+        if lines == 0 {
+            return;
+        }
+        self.game.board.add_garbage(lines, hole);
+        self.garbage.push((lines, hole));
+        if let Some(loc) = self.active {
+            let mut loc = loc;
+            while self.game.board.collides(loc) && loc.y < 64 {
+                loc.y += 1;
+            }
+            self.active = Some(loc).filter(|&l| !self.game.board.collides(l));
+        }
+    }
+
+    // hard drops the piece. the next one doesn't come out until spawn, so garbage can rise in
+    // between. None if there's no piece, or no piece after it to tell whether this one was held
+    pub fn lock(&mut self) -> Result<Option<PlacementInfo>, IllegalMove> {
+        let Some(active) = self.active else {
+            return Ok(None);
+        };
+        let dropped = self.game.board.hard_drop(active);
+        let rotated = self.rotated && dropped == active;
+        // the inputs got the piece there, so if movegen can't the two disagree about the rules
+        let found = find_placement(
+            &self.game.board,
+            dropped,
+            self.rotation_system,
+            self.allow_180,
+        )
+        .ok_or(IllegalMove {
+            index: self.moves.len(),
+            loc: dropped,
+            reason: Illegal::Unreachable,
+        })?;
+        let loc = PieceLocation {
+            spun: found.spun && rotated,
+            ..found
+        };
+        let Some(&next) = self.pieces.get(self.moves.len() + 1) else {
+            self.active = None;
+            return Ok(None);
+        };
+        let info = self.game.advance(next, loc);
+        self.moves.push(ImportedMove {
+            garbage: std::mem::take(&mut self.garbage),
+            loc,
+        });
+        self.active = None;
+        Ok(Some(info))
+    }
+
+    pub fn finish(self) -> ImportedGame {
+        ImportedGame {
+            pieces: self.pieces,
+            moves: self.moves,
+            rotation_system: self.rotation_system,
+            allow_180: self.allow_180,
+        }
+    }
+}
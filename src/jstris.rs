@@ -0,0 +1,304 @@
+use crate::data::*;
+use crate::import::{ImportedGame, Playfield};
+use crate::movegen::RotationSystem;
+
+use serde_json::Value;
+
+// what each replay action does, by id
+const MOVE_LEFT: u32 = 0;
+const MOVE_RIGHT: u32 = 1;
+const DAS_LEFT: u32 = 2;
+const DAS_RIGHT: u32 = 3;
+const ROTATE_LEFT: u32 = 4;
+const ROTATE_RIGHT: u32 = 5;
+const ROTATE_180: u32 = 6;
+const HARD_DROP: u32 = 7;
+const SOFT_DROP_BEGIN_END: u32 = 8;
+const GRAVITY_STEP: u32 = 9;
+const HOLD_BLOCK: u32 = 10;
+const GARBAGE_ADD: u32 = 11;
+const SGARBAGE_ADD: u32 = 12;
+const ARR_MOVE: u32 = 14;
+
+// 6 bits per character, highest first, with each value read lowest bit first
+struct Bits<'a> {
+    values: &'a [u32],
+    index: usize,
+    position: u32,
+}
+
+impl Bits<'_> {
+    // None past the end of the input
+    fn read(&mut self, bits: u32) -> Option<u32> {
+        let mut value = 0;
+        for i in 0..bits {
+            let bit = *self.values.get(self.index)? & self.position;
+            self.position >>= 1;
+            if self.position == 0 {
+                self.position = 32;
+                self.index += 1;
+            }
+            value |= ((bit != 0) as u32) << i;
+        }
+        Some(value)
+    }
+}
+
+// lz-string's decompressFromEncodedURIComponent, which is how jstris packs a replay to share it
+fn decompress(input: &str) -> Option<String> {
+    const KEYS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+-$";
+    // urls turn '+' into spaces
+    let values: Vec<u32> = input
+        .trim()
+        .bytes()
+        .map(|b| if b == b' ' { b'+' } else { b })
+        .map(|b| KEYS.iter().position(|&k| k == b).map(|i| i as u32))
+        .collect::<Option<_>>()?;
+
+    let mut bits = Bits {
+        values: &values,
+        index: 0,
+        position: 32,
+    };
+    let mut read = |n| bits.read(n);
+
+    // 0, 1 and 2 are taken by the codes for a new 8 bit char, a new 16 bit char, and the end
+    let mut dict: Vec<Vec<u16>> = vec![vec![], vec![], vec![]];
+    let mut w = match read(2)? {
+        0 => vec![read(8)? as u16],
+        1 => vec![read(16)? as u16],
+        _ => return Some(String::new()),
+    };
+    dict.push(w.clone());
+    let mut out = w.clone();
+    // codes grow a bit wider every time the dictionary doubles
+    let (mut enlarge_in, mut width) = (4, 3);
+    loop {
+        let code = read(width)?;
+        let code = match code {
+            0 | 1 => {
+                let c = read(if code == 0 { 8 } else { 16 })?;
+                dict.push(vec![c as u16]);
+                enlarge_in -= 1;
+                dict.len() - 1
+            }
+            2 => break,
+            code => code as usize,
+        };
+        if enlarge_in == 0 {
+            enlarge_in = 1 << width;
+            width += 1;
+        }
+        let entry = match code.cmp(&dict.len()) {
+            std::cmp::Ordering::Less => dict[code].clone(),
+            std::cmp::Ordering::Equal => [w.as_slice(), &w[..1]].concat(),
+            std::cmp::Ordering::Greater => return None,
+        };
+        out.extend(&entry);
+        dict.push([w.as_slice(), &entry[..1]].concat());
+        enlarge_in -= 1;
+        if enlarge_in == 0 {
+            enlarge_in = 1 << width;
+            width += 1;
+        }
+        w = entry;
+    }
+    String::from_utf16(&out).ok()
+}
+
+fn base64(input: &str) -> Option<Vec<u8>> {
+    const KEYS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let values: Vec<u32> = input
+        .trim_end_matches('=')
+        .bytes()
+        .map(|b| KEYS.iter().position(|&k| k == b).map(|i| i as u32))
+        .collect::<Option<_>>()?;
+    let mut bytes = vec![];
+    for chunk in values.chunks(4) {
+        let word = chunk
+            .iter()
+            .chain(std::iter::repeat(&0))
+            .take(4)
+            .fold(0, |word, v| word << 6 | v);
+        bytes.extend(&word.to_be_bytes()[1..chunk.len()]);
+    }
+    Some(bytes)
+}
+
+// javascript's `x >>> 0`
+fn uint32(x: f64) -> f64 {
+    x.floor().rem_euclid(4294967296.0)
+}
+
+// the alea generator jstris seeds its randomizer with
+struct Alea {
+    s0: f64,
+    s1: f64,
+    s2: f64,
+    c: f64,
+}
+
+impl Alea {
+    fn new(seed: &str) -> Self {
+        let mut n: f64 = 4022871197.0;
+        let mut mash = |data: &str| {
+            for unit in data.encode_utf16() {
+                n += unit as f64;
+                let mut h = 0.02519603282416938 * n;
+                n = uint32(h);
+                h -= n;
+                h *= n;
+                n = uint32(h);
+                h -= n;
+                n += h * 4294967296.0;
+            }
+            uint32(n) * 2.3283064365386963e-10
+        };
+        let (mut s0, mut s1, mut s2) = (mash(" "), mash(" "), mash(" "));
+        for s in [&mut s0, &mut s1, &mut s2] {
+            *s -= mash(seed);
+            if *s < 0.0 {
+                *s += 1.0;
+            }
+        }
+        Self { s0, s1, s2, c: 1.0 }
+    }
+
+    fn next(&mut self) -> f64 {
+        let t = 2091639.0 * self.s0 + self.c * 2.3283064365386963e-10;
+        self.s0 = self.s1;
+        self.s1 = self.s2;
+        self.c = t.floor();
+        self.s2 = t - self.c;
+        self.s2
+    }
+}
+
+// jstris' 7-bag, drawing one piece at a time out of what's left in the bag
+struct Bag {
+    rng: Alea,
+    bag: Vec<Piece>,
+}
+
+impl Bag {
+    fn next(&mut self) -> Piece {
+        if self.bag.is_empty() {
+            // jstris numbers pieces in this order
+            self.bag = vec![
+                Piece::I,
+                Piece::O,
+                Piece::T,
+                Piece::L,
+                Piece::J,
+                Piece::S,
+                Piece::Z,
+            ];
+        }
+        let i = (self.rng.next() * self.bag.len() as f64) as usize;
+        self.bag.remove(i.min(self.bag.len() - 1))
+    }
+}
+
+// a game from a jstris replay, either as shared (lz-string compressed) or as the json inside. the
+// actions are 3 byte words of a 20 bit time in ms and a 4 bit action id, and adding garbage is
+// followed by a word with the lines in its time and the hole column in its id. the config's `rs`
+// picks the rotation system, and only jstris' srs (0, also what's used when it's missing) can be
+// played back. jstris has no setting that turns 180s off, so they're always allowed, with its own
+// kicks. soft drop is played back as instant, slower soft drops show up as gravity steps anyway
+pub fn import(replay: &str) -> Result<ImportedGame, ParseError> {
+    let json = match replay.trim_start().starts_with('{') {
+        true => replay.to_string(),
+        false => decompress(replay)
+            .ok_or_else(|| ParseError("replay isn't json or lz-string".to_string()))?,
+    };
+    let replay: Value = serde_json::from_str(&json)
+        .map_err(|e| ParseError(format!("replay isn't valid json: {e}")))?;
+    let seed = match &replay["c"]["seed"] {
+        Value::String(seed) => seed.clone(),
+        Value::Number(seed) => seed.to_string(),
+        _ => return Err(ParseError("replay has no seed".to_string())),
+    };
+    let system = match replay["c"]["rs"].as_u64().unwrap_or(0) {
+        0 => RotationSystem::Jstris,
+        rs => {
+            return Err(ParseError(format!(
+                "replay uses rotation system {rs}, only srs (0) can be played back"
+            )))
+        }
+    };
+    let data = replay["d"]
+        .as_str()
+        .and_then(base64)
+        .ok_or_else(|| ParseError("replay has no actions".to_string()))?;
+    let mut actions = data
+        .chunks_exact(3)
+        .map(|w| (w[0] as u32) << 16 | (w[1] as u32) << 8 | w[2] as u32)
+        .map(|word| (word >> 4, word & 15));
+
+    let mut bag = Bag {
+        rng: Alea::new(&seed),
+        bag: vec![],
+    };
+    let pieces = (0..14).map(|_| bag.next()).collect();
+    let mut field = Playfield::new(pieces, system, true);
+    let mut soft = false;
+    // ARR_MOVE repeats the last direction
+    let mut dir = 0;
+    while let Some((_, action)) = actions.next() {
+        while field.pieces.len() < field.spawned() + 8 {
+            let piece = bag.next();
+            field.pieces.push(piece);
+        }
+        let Some(loc) = field.active else {
+            break;
+        };
+        match action {
+            MOVE_LEFT | MOVE_RIGHT => {
+                dir = if action == MOVE_LEFT { -1 } else { 1 };
+                field.shift(dir);
+            }
+            DAS_LEFT | DAS_RIGHT => {
+                dir = if action == DAS_LEFT { -1 } else { 1 };
+                field.slide(dir);
+            }
+            ARR_MOVE => {
+                field.shift(dir);
+            }
+            ROTATE_LEFT => {
+                field.rotate(loc.rotation.rotate_left());
+            }
+            ROTATE_RIGHT => {
+                field.rotate(loc.rotation.rotate_right());
+            }
+            ROTATE_180 => {
+                field.rotate(loc.rotation.rotate_180());
+            }
+            HARD_DROP => {
+                field
+                    .lock()
+                    .map_err(|e| ParseError(format!("replay doesn't play back, {e}")))?;
+                field.spawn();
+            }
+            SOFT_DROP_BEGIN_END => soft = !soft,
+            GRAVITY_STEP => {
+                field.drop();
+            }
+            HOLD_BLOCK => {
+                field.hold();
+            }
+            GARBAGE_ADD | SGARBAGE_ADD => {
+                let Some((lines, hole)) = actions.next() else {
+                    break;
+                };
+                // solid garbage has no hole
+                let hole = if action == SGARBAGE_ADD { 10 } else { hole };
+                field.add_garbage(lines.min(40), hole as usize);
+            }
+            _ => {}
+        }
+        if soft {
+            field.sonic_drop();
+        }
+    }
+    Ok(field.finish())
+}
@@ -1,18 +1,47 @@
+pub mod analyze;
 pub mod beam_search;
 pub mod data;
 pub mod eval;
-// pub mod genetic;
+pub mod fitness;
+pub mod fumen;
+#[cfg(feature = "tuner")]
+pub mod genetic;
+pub mod image;
+pub mod import;
+pub mod jstris;
 pub mod movegen;
+pub mod movegen_cache;
+pub mod practice;
+pub mod render;
+pub mod replay;
+pub mod simulate;
+#[cfg(feature = "tuner")]
+pub mod spsa;
+pub mod tetrio;
+pub mod versus;
 
 #[cfg(test)]
 mod tests {
+    use super::analyze::*;
+    use super::beam_search::*;
     use super::data::*;
+    use super::eval::Eval;
+    use super::fitness::*;
     use super::movegen::*;
+    use super::movegen_cache::*;
+    use super::practice::*;
+    use super::render::*;
+    use super::replay::*;
+    use super::simulate::*;
+    use super::versus::*;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
 
     #[test]
     fn movegen() {
         let mut game = Game::new(None);
-        game.board.cols = [
+        game.board.set_cols([
             Column(7),
             Column(127),
             Column(31),
@@ -23,7 +52,7 @@ mod tests {
             Column(15),
             Column(15),
             Column(15),
-        ];
+        ]);
         println!(
             "{:?}",
             movegen_piece(&game.board, Piece::T)
@@ -37,4 +66,980 @@ mod tests {
                 .collect::<String>()
         );
     }
+
+    #[test]
+    fn search_path() {
+        let game = Game::new(Some(Piece::I));
+        let queue = [Piece::T, Piece::S, Piece::Z, Piece::L, Piece::J];
+        let config = SearchConfig {
+            depth: 5,
+            width: 200,
+            seed: Some(1),
+            ..SearchConfig::default()
+        };
+        let eval = Eval::from([-1.0; 14]);
+        let search_loc = candidates(&game, queue[0], &config);
+        let results = search_results(&game, &search_loc, &queue, &eval, &config).unwrap();
+        let best = results.best().unwrap();
+        let path = results.path(best);
+
+        assert_eq!(path.len(), results.stats.depth);
+        assert_eq!(path[0].0, search_loc[best.id]);
+        assert_eq!(path.last().unwrap().0, best.loc);
+
+        // replaying the path from the root has to end up on the same board
+        let mut replayed = game;
+        for (&(loc, _), &next) in path.iter().zip(&queue) {
+            replayed.advance(next, loc);
+        }
+        assert_eq!(replayed.board.cols(), best.game.board.cols());
+    }
+
+    #[test]
+    fn root_quota() {
+        let game = Game::new(Some(Piece::I));
+        let queue = [Piece::T, Piece::S, Piece::Z, Piece::L];
+        let config = SearchConfig {
+            depth: 4,
+            width: 100,
+            seed: Some(1),
+            root_quota: Some(3),
+            ..SearchConfig::default()
+        };
+        let eval = Eval::from([-1.0; 14]);
+        let search_loc = candidates(&game, queue[0], &config);
+        let results = search_results(&game, &search_loc, &queue, &eval, &config).unwrap();
+
+        let mut counts = vec![0; search_loc.len()];
+        for node in &results.nodes {
+            counts[node.id] += 1;
+        }
+        assert!(counts.iter().all(|&c| c <= 3));
+        assert!(counts.iter().filter(|&&c| c > 0).count() > 1);
+    }
+
+    #[test]
+    fn search_errors() {
+        let eval = Eval::from([-1.0; 14]);
+        let config = SearchConfig {
+            depth: 2,
+            width: 10,
+            ..SearchConfig::default()
+        };
+        let mut game = Game::new(Some(Piece::I));
+        assert_eq!(
+            search(&game, &[], &eval, &config),
+            Err(SearchError::EmptyQueue)
+        );
+        let no_quota = SearchConfig {
+            root_quota: Some(0),
+            ..config
+        };
+        assert!(no_quota.validate().is_err());
+        assert!(config.validate().is_ok());
+
+        // stack right up into the spawn rows
+        let mut cols = [Column((1 << 22) - 1); 10];
+        cols[0] = Column(0);
+        game.board.set_cols(cols);
+        assert_eq!(
+            search(&game, &[Piece::T, Piece::O], &eval, &config),
+            Err(SearchError::ToppedOut)
+        );
+
+        // way too tall for movegen, but the spawn area itself is open
+        let mut cols = [Column(0); 10];
+        cols[0] = Column(u64::MAX);
+        cols[9] = Column(u64::MAX);
+        game.board.set_cols(cols);
+        assert_eq!(
+            search(&game, &[Piece::T, Piece::O], &eval, &config),
+            Err(SearchError::NoMoves)
+        );
+    }
+
+    #[test]
+    fn attack() {
+        let mut game = Game::new(Some(Piece::T));
+        let mut cols = [Column(0b1111); 10];
+        cols[9] = Column(0);
+        game.board.set_cols(cols);
+        game.set_b2b(1);
+        let info = game.advance(
+            Piece::I,
+            PieceLocation {
+                piece: Piece::I,
+                rotation: Rotation::East,
+                spun: false,
+                x: 9,
+                y: 2,
+                possible_line_clear: true,
+            },
+        );
+        // quad + b2b + perfect clear
+        assert_eq!(info.lines_cleared, 4);
+        assert_eq!(info.attack, 4 + 1 + 10);
+        assert_eq!((game.b2b(), game.combo()), (2, 1));
+    }
+
+    #[test]
+    fn fitness_is_reproducible() {
+        let eval = Eval::from([-1.0; 14]);
+        let config = FitnessConfig {
+            sim: SimConfig {
+                games: 2,
+                pieces: 30,
+                garbage: Some(GarbageConfig::default()),
+                search: SearchConfig {
+                    depth: 2,
+                    width: 20,
+                    preview: 1,
+                    ..SearchConfig::default()
+                },
+                ..SimConfig::default()
+            },
+            ..FitnessConfig::default()
+        };
+        assert_eq!(evaluate(&eval, &config), evaluate(&eval, &config));
+
+        // the old tuner played 500 pieces and scored 250 * max b2b / 500
+        let stats = GameStats {
+            max_b2b: 10,
+            ..GameStats::default()
+        };
+        assert_eq!(score(&stats, Metric::MaxB2b, 500), 5.0);
+    }
+
+    #[test]
+    fn elo_ordering() {
+        let record = |a, b, score| GameRecord {
+            a,
+            b,
+            score,
+            result: MatchResult {
+                outcome: Outcome::Draw,
+                pieces: [0, 0],
+                attack: [0, 0],
+            },
+        };
+        // 0 beats 1 beats 2, and 0 beats 2 every time
+        let mut games = vec![];
+        for _ in 0..10 {
+            games.push(record(0, 1, 1.0));
+            games.push(record(1, 2, 1.0));
+            games.push(record(0, 2, 1.0));
+        }
+        games.push(record(0, 1, 0.0));
+        let elo = elo(3, &games);
+        assert!(elo[0] > elo[1] && elo[1] > elo[2]);
+        assert!(elo.iter().all(|e| e.is_finite()));
+        assert!(elo.iter().sum::<f64>().abs() < 1e-6);
+    }
+
+    #[test]
+    fn analyze_position() {
+        let text: Board = ".#########".parse().unwrap();
+        let fumen = parse_board("v115@chI8JeAgH").unwrap();
+        assert_eq!(text.cols(), fumen.cols());
+        assert!(parse_board("v115@vhAAgH").unwrap().is_empty());
+        assert!(parse_board("#####").is_err());
+        let empty = ColoredBoard::new(parse_board("").unwrap());
+        assert_eq!(super::fumen::encode(&empty), "v115@vhAAgH");
+        let fumen = super::fumen::decode_colored("v115@chI8JeAgH").unwrap();
+        assert_eq!(super::fumen::encode(&fumen), "v115@chI8JeAgH");
+
+        let mut game = Game::new(Some(Piece::I));
+        game.board = text;
+        let queue = parse_queue("TSZL").unwrap();
+        let config = SearchConfig {
+            depth: 4,
+            width: 200,
+            seed: Some(1),
+            root_quota: Some(5),
+            ..SearchConfig::default()
+        };
+        let eval = Eval::from([-1.0; 14]);
+        let analysis = analyze(&game, &queue, &eval, &config, 5).unwrap();
+
+        assert_eq!(analysis.candidates.len(), 5);
+        for pair in analysis.candidates.windows(2) {
+            assert!(pair[0].score >= pair[1].score);
+            assert_ne!(pair[0].loc, pair[1].loc);
+        }
+        for c in &analysis.candidates {
+            assert_eq!(c.line.len(), 4);
+            assert_eq!(c.line[0].0, c.loc);
+        }
+    }
+
+    #[test]
+    fn replay_player() {
+        let eval = Eval::from([-1.0; 14]);
+        let config = SearchConfig {
+            depth: 2,
+            width: 50,
+            preview: 1,
+            seed: Some(0),
+            ..SearchConfig::default()
+        };
+        let (hold, queue) = gen_queue(2, &mut StdRng::seed_from_u64(5));
+        let mut game = Game::new(Some(hold));
+        let mut replay = Replay::new(5, eval, config, hold);
+        replay.queue = queue.clone();
+        for i in 0..8 {
+            let (loc, stats) = search_with_stats(&game, &queue[i..], &eval, &config).unwrap();
+            let info = game.advance(queue[i], loc);
+            replay.moves.push(Move {
+                piece: queue[i],
+                loc,
+                info,
+                stats,
+            });
+        }
+        let replay: Replay =
+            serde_json::from_str(&serde_json::to_string(&replay).unwrap()).unwrap();
+
+        let mut player = Player::new(&replay).unwrap();
+        assert!(!player.back());
+        while player.forward() {}
+        assert_eq!(player.position(), 8);
+        assert_eq!(player.game().board.cols(), game.board.cols());
+        // nothing but placed pieces on this board, so every filled cell has a color
+        for x in 0..10 {
+            for y in 0..20 {
+                let filled = game.board.cols()[x].0 & 1 << y != 0;
+                assert_eq!(player.colors().get(x, y).is_some(), filled);
+            }
+        }
+        assert!(player.back());
+        assert_eq!(player.queue()[0], queue[7]);
+        assert_eq!(player.next_move().unwrap().loc, replay.moves[7].loc);
+        assert!(!player.seek(9));
+
+        let filmstrip = super::image::Filmstrip::new(&replay, 6, 5, 3).unwrap();
+        let views = filmstrip.views();
+        assert_eq!(views.len(), 3);
+        let svg = super::image::svg(&views, 10);
+        assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+        assert!(svg.contains("after move 8"));
+        assert!(super::image::has_text(&views));
+        assert!(!super::image::has_text(&[View::new(&game.board)]));
+
+        // a hand edited replay is turned away instead of panicking
+        let mut broken = replay.clone();
+        broken.moves[2].loc.x = 12;
+        let error = Player::new(&broken).err().unwrap();
+        assert!(error.0.starts_with("move 3 "), "{error}");
+        let mut broken = replay.clone();
+        broken.moves[5].loc = broken.moves[4].loc;
+        broken.moves[5].piece = broken.moves[4].loc.piece;
+        assert!(Player::new(&broken).is_err());
+    }
+
+    #[test]
+    fn render_views() {
+        let board: Board = "#########.".parse().unwrap();
+        let loc = PieceLocation {
+            piece: Piece::I,
+            rotation: Rotation::East,
+            spun: false,
+            x: 9,
+            y: 6,
+            possible_line_clear: true,
+        };
+        let view = View::new(&board).piece(loc).ghost(true).hold(Piece::T);
+        let expected = [
+            "..........   hold  T",
+            ".........I",
+            ".........I",
+            ".........I",
+            ".........I",
+            ".........+",
+            ".........+",
+            ".........+",
+            "#########+",
+        ];
+        assert_eq!(view.render(Style::Ascii), expected.join("\n"));
+
+        let empty = Board::new([Column(0); 10]);
+        let views = [View::new(&board).title("a"), View::new(&empty).height(3)];
+        let expected = [
+            "a                ..........",
+            "..........       ..........",
+            "#########.       ..........",
+        ];
+        assert_eq!(side_by_side(&views, Style::Ascii, 7), expected.join("\n"));
+    }
+
+    #[test]
+    fn colored_board() {
+        let mut board = ColoredBoard::new(".#########".parse().unwrap());
+        board.add_garbage(1, 0);
+        let i = PieceLocation {
+            piece: Piece::I,
+            rotation: Rotation::East,
+            spun: false,
+            x: 0,
+            y: 2,
+            possible_line_clear: true,
+        };
+        let t = PieceLocation {
+            piece: Piece::T,
+            rotation: Rotation::North,
+            spun: false,
+            x: 5,
+            y: 0,
+            possible_line_clear: false,
+        };
+        let info = board.place(i);
+        assert_eq!(info.line_mask, 0b11);
+        board.place(t);
+        assert_eq!(board.board.max_height(), 2);
+        for x in 0..10 {
+            for y in 0..4 {
+                let filled = board.board.cols()[x].0 & 1 << y != 0;
+                assert_eq!(board.get(x, y).is_some(), filled);
+            }
+        }
+        assert_eq!(board.get(0, 1), Some(Piece::I));
+        assert_eq!(board.get(5, 1), Some(Piece::T));
+
+        let fumen = super::fumen::encode(&board);
+        let decoded = super::fumen::decode_colored(&fumen).unwrap();
+        assert_eq!(decoded.board.cols(), board.board.cols());
+        assert_eq!(decoded.colors, board.colors);
+    }
+
+    #[test]
+    fn practice_session() {
+        let board = Board::new([Column(0); 10]);
+        for loc in movegen_piece(&board, Piece::T) {
+            assert_eq!(
+                find_placement(&board, loc, RotationSystem::SrsPlus, true),
+                Some(loc)
+            );
+        }
+        let spawn = Piece::T.spawn();
+        let turned = rotate(&board, spawn, Rotation::East, RotationSystem::SrsPlus).unwrap();
+        assert_eq!(turned.rotation, Rotation::East);
+
+        let config = SearchConfig {
+            depth: 3,
+            width: 100,
+            preview: 3,
+            seed: Some(1),
+            ..SearchConfig::default()
+        };
+        let mut practice = Practice::new(3, Eval::default(), config);
+        let (first, hold) = (practice.queue[0], practice.game.hold());
+        practice.input(Input::Hold);
+        assert_eq!(practice.active.piece, hold);
+        // only one hold per piece
+        practice.input(Input::Hold);
+        assert_eq!(practice.active.piece, hold);
+
+        practice.input(Input::Left);
+        let comparison = practice.input(Input::HardDrop).unwrap();
+        assert_eq!(comparison.human.piece, hold);
+        assert!(comparison.bot.is_some());
+        assert_eq!(practice.pieces, 1);
+        assert_eq!(practice.game.hold(), first);
+        let cells: u32 = practice
+            .game
+            .board
+            .cols()
+            .iter()
+            .map(|c| c.0.count_ones())
+            .sum();
+        assert_eq!(cells, 4);
+        assert!(practice.queue.len() > config.preview);
+
+        // turning the o does nothing
+        practice.active = Piece::O.spawn();
+        for input in [Input::RotateCw, Input::RotateCcw, Input::Rotate180] {
+            practice.input(input);
+            assert_eq!(practice.active.blocks(), Piece::O.spawn().blocks());
+            assert_eq!(practice.active, Piece::O.spawn());
+        }
+
+        // refills keep the pieces in whole bags, counting the one that started in hold
+        let mut practice = Practice::new(5, Eval::default(), config);
+        let mut seen = vec![practice.game.hold()];
+        for _ in 0..40 {
+            practice.game.board = Board::new([Column(0); 10]);
+            seen.push(practice.queue[0]);
+            practice.input(Input::HardDrop).unwrap();
+        }
+        seen.extend(&practice.queue);
+        for bag in seen.chunks_exact(7) {
+            let mut bag = bag.to_vec();
+            bag.sort_by_key(|&p| p as usize);
+            assert_eq!(
+                bag,
+                [
+                    Piece::I,
+                    Piece::O,
+                    Piece::T,
+                    Piece::L,
+                    Piece::J,
+                    Piece::S,
+                    Piece::Z
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn tetrio_import() {
+        let key = |frame: u64, down: bool, key: &str| {
+            let kind = if down { "keydown" } else { "keyup" };
+            serde_json::json!({"frame": frame, "type": kind, "data": {"key": key, "subframe": 0.0}})
+        };
+        let events = vec![
+            serde_json::json!({"frame": 0, "type": "full", "data": {"options": {
+                "seed": 12345, "bagtype": "7-bag", "g": 0.0,
+                "handling": {"das": 8, "arr": 0, "sdf": 41},
+            }}}),
+            key(5, true, "hardDrop"),
+            key(6, false, "hardDrop"),
+            // tapped once, then held past das all the way to the wall
+            key(10, true, "moveLeft"),
+            key(11, false, "moveLeft"),
+            key(12, true, "hardDrop"),
+            key(20, true, "moveRight"),
+            key(40, true, "hardDrop"),
+            key(41, false, "moveRight"),
+            serde_json::json!({"frame": 42, "type": "ige", "data": {"type": "ige", "data": {
+                "type": "garbage", "amt": 2, "column": 3,
+            }}}),
+            key(80, true, "hold"),
+            key(81, true, "hardDrop"),
+            key(90, true, "hardDrop"),
+        ];
+        let replay = serde_json::json!({"data": {"events": events}}).to_string();
+        let game = super::tetrio::import(&replay, 0, 0).unwrap();
+        assert_eq!(game.moves.len(), 5);
+        let mut bag = game.pieces[..7].to_vec();
+        bag.sort_by_key(|p| *p as u8);
+        assert_eq!(
+            bag,
+            [
+                Piece::I,
+                Piece::O,
+                Piece::T,
+                Piece::L,
+                Piece::J,
+                Piece::S,
+                Piece::Z
+            ]
+        );
+
+        let positions = game.positions(5).unwrap();
+        let blocks = |loc: PieceLocation| loc.blocks().map(|(x, _)| x);
+        let spawn_xs = |piece: Piece| blocks(piece.spawn());
+        assert_eq!(blocks(game.moves[0].loc), spawn_xs(game.pieces[0]));
+        assert_eq!(
+            blocks(game.moves[1].loc),
+            spawn_xs(game.pieces[1]).map(|x| x - 1)
+        );
+        assert_eq!(blocks(game.moves[2].loc).iter().max(), Some(&9));
+        // the garbage waits 20 frames and comes up after the next piece that clears nothing
+        assert!(game.moves[3].garbage.is_empty());
+        assert_eq!(game.moves[4].garbage, [(2, 3)]);
+        assert_eq!(positions[4].game.board.cols()[3].0 & 0b11, 0);
+        assert_eq!(positions[4].game.board.cols()[4].0 & 0b11, 0b11);
+        // the hold swapped the fourth piece out for the fifth
+        assert_eq!(game.moves[3].loc.piece, game.pieces[4]);
+        assert_eq!(positions[3].game.hold(), game.pieces[3]);
+    }
+
+    #[test]
+    fn jstris_import() {
+        // hard drop twice, tap left, das right, hold, then garbage before the last drop
+        let json = r#"{"c":{"v":3.3,"seed":"f2ZzK","m":1},"d":"AAAHAAZHAAyAABLHABkDAB9HACWKACvHADILAAAjADhH"}"#;
+        let shared = "N4IgxiBcoG5QzAOngGhAZwKaYCZRAGYBMAWgF4DSIaAtlAIwC+aekIAgpwBKck-sBPTgCEAMj2EBrACLthATh4BhAOoV2SmD2kBJUZ3YArdtIAWXEIyA";
+        let game = super::jstris::import(json).unwrap();
+        assert_eq!(
+            game.pieces[..14],
+            parse_queue("LJZSIOTJLISZOT").unwrap()[..]
+        );
+        let from_shared = super::jstris::import(shared).unwrap();
+        assert_eq!(
+            serde_json::to_string(&from_shared).unwrap(),
+            serde_json::to_string(&game).unwrap()
+        );
+
+        assert_eq!(game.rotation_system, RotationSystem::Jstris);
+        assert!(game.allow_180);
+        assert_eq!(game.moves.len(), 6);
+        let xs = |loc: PieceLocation| loc.blocks().map(|(x, _)| x);
+        assert_eq!(xs(game.moves[2].loc), xs(Piece::Z.spawn()).map(|x| x - 1));
+        assert_eq!(xs(game.moves[3].loc).iter().max(), Some(&9));
+        assert_eq!(game.moves[4].loc.piece, Piece::O);
+        assert_eq!(game.moves[5].garbage, [(2, 3)]);
+        let positions = game.positions(5).unwrap();
+        assert_eq!(positions[5].game.hold(), Piece::I);
+        assert_eq!(positions[5].game.board.cols()[3].0 & 0b11, 0);
+
+        // a 180 right at the start, then a hard drop
+        let turned = r#"{"c":{"seed":"f2ZzK"},"d":"AAAGAAAH"}"#;
+        let game = super::jstris::import(turned).unwrap();
+        assert_eq!(game.moves[0].loc.piece, Piece::L);
+        assert_eq!(game.moves[0].loc.rotation, Rotation::South);
+        assert!(game.positions(5).is_ok());
+        // a t flat on the floor can only turn over by going up a row
+        let floor = PieceLocation {
+            y: 0,
+            ..Piece::T.spawn()
+        };
+        let board = Board::new([Column(0); 10]);
+        let over = rotate(&board, floor, Rotation::South, RotationSystem::Jstris).unwrap();
+        assert_eq!((over.x, over.y), (floor.x, 1));
+        assert_eq!(
+            rotate(&board, floor, Rotation::South, RotationSystem::Srs),
+            None
+        );
+        // other rotation systems aren't played back
+        let ars = r#"{"c":{"seed":"f2ZzK","rs":1},"d":"AAAH"}"#;
+        assert!(super::jstris::import(ars).is_err());
+    }
+
+    #[test]
+    fn infer_from_boards() {
+        let before: Board = "...#######/#.########".parse().unwrap();
+        let cases = [(Piece::T, Piece::O), (Piece::O, Piece::T)];
+        for (active, hold) in cases {
+            // an upside down t fills the hole and the gap above it, clearing both lines
+            let loc = movegen_piece(&before, Piece::T)
+                .into_iter()
+                .find(|loc| {
+                    let mut board = before;
+                    board.place(*loc).lines_cleared == 2
+                })
+                .unwrap();
+            let mut after = before;
+            after.place(loc);
+            assert!(after.is_empty());
+
+            let found = super::import::infer_placements(
+                &before,
+                &after,
+                active,
+                Some(hold),
+                RotationSystem::SrsPlus,
+                true,
+            );
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].loc, loc);
+            assert_eq!(found[0].info.line_mask, 0b11);
+            assert_eq!(found[0].held, active != Piece::T);
+        }
+    }
+
+    #[test]
+    fn placement_legality() {
+        // a roof with a one wide gap on the right
+        let board: Board = "#########./........../..........".parse().unwrap();
+        let at = |piece, rotation, x, y| PieceLocation {
+            piece,
+            rotation,
+            spun: false,
+            x,
+            y,
+            possible_line_clear: true,
+        };
+        let under_roof = at(Piece::O, Rotation::North, 0, 0);
+        let on_roof = at(Piece::T, Rotation::North, 4, 3);
+        let floating = at(Piece::T, Rotation::North, 4, 6);
+        let in_roof = at(Piece::T, Rotation::North, 4, 2);
+        let off_board = at(Piece::I, Rotation::North, 8, 3);
+
+        let system = RotationSystem::SrsPlus;
+        assert!(is_reachable(&board, on_roof, system, true));
+        assert!(!is_reachable(&board, under_roof, system, true));
+        assert!(!is_reachable(&board, floating, system, true));
+        assert!(!is_reachable(&board, in_roof, system, true));
+        // an l that has to turn 180 to tuck under the overhang
+        let overhang: Board = ".....#.##./..........".parse().unwrap();
+        let tucked = PieceLocation {
+            spun: true,
+            ..at(Piece::L, Rotation::East, 6, 1)
+        };
+        assert!(is_reachable(&overhang, tucked, system, true));
+        assert!(!is_reachable(&overhang, tucked, system, false));
+
+        let mut placed = board;
+        assert_eq!(
+            placed.try_place(off_board).err(),
+            Some(PlaceError::OutOfBounds)
+        );
+        assert_eq!(placed.try_place(in_roof).err(), Some(PlaceError::Overlaps));
+        assert_eq!(placed.cols(), board.cols());
+        assert!(placed.try_place(under_roof).is_ok());
+
+        let imported = |loc| super::import::ImportedGame {
+            pieces: vec![Piece::T, Piece::O, Piece::I],
+            moves: vec![super::import::ImportedMove {
+                garbage: vec![],
+                loc,
+            }],
+            rotation_system: system,
+            allow_180: true,
+        };
+        let on_floor = at(Piece::T, Rotation::North, 4, 0);
+        assert!(imported(on_floor).positions(3).is_ok());
+        let err = imported(floating).positions(3).unwrap_err();
+        assert_eq!(err.reason, super::import::Illegal::Unreachable);
+        assert_eq!(
+            err.to_string(),
+            "move 1 (T North x=4 y=6): the piece can't get there"
+        );
+        let err = imported(at(Piece::I, Rotation::North, 4, 0))
+            .positions(3)
+            .unwrap_err();
+        assert_eq!(err.reason, super::import::Illegal::WrongPiece);
+
+        // a saved move that says it can't clear a line still clears it
+        let flat = |piece, x| super::import::ImportedMove {
+            garbage: vec![],
+            loc: PieceLocation {
+                possible_line_clear: false,
+                ..at(piece, Rotation::North, x, 0)
+            },
+        };
+        let game = super::import::ImportedGame {
+            pieces: vec![Piece::I, Piece::I, Piece::I, Piece::O, Piece::T],
+            moves: vec![
+                flat(Piece::I, 1),
+                flat(Piece::I, 5),
+                flat(Piece::O, 8),
+                flat(Piece::T, 1),
+            ],
+            rotation_system: system,
+            allow_180: false,
+        };
+        let positions = game.positions(3).unwrap();
+        assert!(positions[2].loc.possible_line_clear);
+        let cols = positions[3].game.board.cols();
+        assert_eq!(cols.map(|c| c.0), [0, 0, 0, 0, 0, 0, 0, 0, 1, 1]);
+
+        // an o turning in a replay stays where it is
+        let mut field = super::import::Playfield::new(vec![Piece::O, Piece::T], system, true);
+        assert!(field.rotate(Rotation::East));
+        assert_eq!(field.active, Some(Piece::O.spawn()));
+    }
+
+    #[test]
+    fn game_history() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let (hold, queue) = gen_queue(6, &mut rng);
+        let config = SearchConfig {
+            depth: 2,
+            width: 50,
+            preview: 2,
+            seed: Some(2),
+            ..SearchConfig::default()
+        };
+        let eval = Eval::default();
+        let mut history = History::new(Game::new(Some(hold)));
+        let mut snapshots = vec![];
+        let mut cleared = 0;
+        for i in 0..30 {
+            snapshots.push(history.game);
+            let loc = search(&history.game, &queue[i..], &eval, &config).unwrap();
+            cleared += history.advance(queue[i], loc).lines_cleared;
+        }
+        assert!(cleared > 0);
+        let end = history.game;
+
+        let same = |a: &Game, b: &Game| {
+            a.board.cols() == b.board.cols()
+                && a.hold() == b.hold()
+                && a.b2b() == b.b2b()
+                && a.b2b_deficit == b.b2b_deficit
+                && a.combo() == b.combo()
+        };
+        for snapshot in snapshots.iter().rev() {
+            assert!(history.undo().is_some());
+            assert!(same(&history.game, snapshot));
+        }
+        assert!(history.undo().is_none());
+        while history.redo().is_some() {}
+        assert!(same(&history.game, &end));
+        assert_eq!(history.moves().len(), 30);
+
+        history.undo();
+        assert!(history.can_redo());
+        history.advance(queue[29], history.moves()[0].loc);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn zobrist_hash() {
+        let fresh = |game: &Game| {
+            let mut game = *game;
+            game.rehash();
+            game.hash()
+        };
+        let mut rng = StdRng::seed_from_u64(9);
+        let (hold, queue) = gen_queue(6, &mut rng);
+        let config = SearchConfig {
+            depth: 2,
+            width: 50,
+            preview: 2,
+            seed: Some(3),
+            ..SearchConfig::default()
+        };
+        let eval = Eval::default();
+        let mut history = History::new(Game::new(Some(hold)));
+        let mut hashes = vec![];
+        let mut cleared = 0;
+        for i in 0..30 {
+            if i == 5 || i == 15 {
+                history.game.board.add_garbage(2, i % 10);
+            }
+            hashes.push(history.game.hash());
+            let loc = search(&history.game, &queue[i..], &eval, &config).unwrap();
+            cleared += history.advance(queue[i], loc).lines_cleared;
+            assert_eq!(history.game.hash(), fresh(&history.game));
+        }
+        assert!(cleared > 0);
+        // undo puts back the hash too, as long as no garbage came up in between
+        for &hash in hashes[20..].iter().rev() {
+            history.undo();
+            assert_eq!(history.game.hash(), hash);
+        }
+
+        // the same cells hash the same however they got there
+        let played = history.game.board;
+        let rows: Vec<String> = (0..played.max_height())
+            .rev()
+            .map(|y| {
+                (0..10)
+                    .map(|x| match played.cols()[x].0 & 1 << y {
+                        0 => '.',
+                        _ => '#',
+                    })
+                    .collect()
+            })
+            .collect();
+        let board: Board = rows.join("/").parse().unwrap();
+        assert_eq!(board.hash(), history.game.board.hash());
+        let json = serde_json::to_string(&board).unwrap();
+        let loaded: Board = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.hash(), board.hash());
+
+        // the setters keep the hash up to date, and setting a value back undoes it
+        let mut other = history.game;
+        let hold = other.hold();
+        other.set_hold(if hold == Piece::T { Piece::I } else { Piece::T });
+        assert_ne!(other.hash(), history.game.hash());
+        assert_eq!(other.hash(), fresh(&other));
+        other.set_hold(hold);
+        assert_eq!(other.hash(), history.game.hash());
+        for set in [
+            |g: &mut Game| g.set_b2b(g.b2b() + 1),
+            |g: &mut Game| g.set_combo(g.combo() + 1),
+        ] {
+            let mut other = history.game;
+            set(&mut other);
+            assert_ne!(other.hash(), history.game.hash());
+            assert_eq!(other.hash(), fresh(&other));
+        }
+    }
+
+    #[test]
+    fn movegen_cache() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let (hold, queue) = gen_queue(2, &mut rng);
+        let game = Game::new(Some(hold));
+        let config = SearchConfig {
+            depth: 4,
+            width: 300,
+            seed: Some(1),
+            ..SearchConfig::default()
+        };
+        let cached = SearchConfig {
+            movegen_cache: Some(1000),
+            ..config
+        };
+        let eval = Eval::default();
+        let search_loc = candidates(&game, queue[0], &config);
+        let plain = search_results(&game, &search_loc, &queue, &eval, &config).unwrap();
+        let results = search_results(&game, &search_loc, &queue, &eval, &cached).unwrap();
+        assert_eq!(results.stats.nodes, plain.stats.nodes);
+        assert_eq!(results.best().unwrap().loc, plain.best().unwrap().loc);
+        assert!(results.stats.cache.hits > 0);
+        assert_eq!(plain.stats.cache.hits + plain.stats.cache.misses, 0);
+
+        // the least recently used board goes first
+        let mut cache = MovegenCache::new(2, RotationSystem::SrsPlus, true);
+        let boards: Vec<Board> = ["#.........", ".#........", "..#......."]
+            .iter()
+            .map(|b| b.parse().unwrap())
+            .collect();
+        assert_eq!(
+            cache.get(&boards[0], Piece::T),
+            movegen_piece(&boards[0], Piece::T)
+        );
+        cache.get(&boards[1], Piece::T);
+        cache.get(&boards[0], Piece::T);
+        cache.get(&boards[2], Piece::T);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats.hits, 1);
+        cache.get(&boards[0], Piece::T);
+        cache.get(&boards[1], Piece::T);
+        assert_eq!((cache.stats.hits, cache.stats.misses), (2, 4));
+    }
+
+    // a few short games with a shallow search, enough for the tuners to have something to score
+    #[cfg(feature = "tuner")]
+    fn tuner_fitness() -> FitnessConfig {
+        FitnessConfig {
+            metric: Metric::LinesSurvived,
+            sim: SimConfig {
+                games: 1,
+                pieces: 20,
+                search: SearchConfig {
+                    depth: 2,
+                    width: 20,
+                    preview: 1,
+                    seed: Some(0),
+                    ..SearchConfig::default()
+                },
+                ..SimConfig::default()
+            },
+        }
+    }
+
+    #[cfg(feature = "tuner")]
+    #[test]
+    fn genetic_run() {
+        use super::genetic::*;
+
+        let config = GeneticConfig {
+            population: 4,
+            generations: 2,
+            crossovers: 2,
+            mutations: 2,
+            tournament: 3,
+            mutation_strength: 20.0,
+            fitness: tuner_fitness(),
+        };
+        let out = std::env::temp_dir().join(format!("tetrizz-genetic-{}.json", std::process::id()));
+        let run = || run_genetic_algo(Checkpoint::new(config, 7), &out, None).unwrap();
+        let best = run();
+        assert_eq!(best.weights, run().weights);
+        assert!(best.fitness > 0.0);
+        assert_eq!(Eval::load(&out).unwrap().weights(), best.weights);
+        std::fs::remove_file(&out).unwrap();
+
+        for bad in [
+            GeneticConfig {
+                population: 1,
+                tournament: 1,
+                ..config
+            },
+            GeneticConfig {
+                tournament: 5,
+                ..config
+            },
+            GeneticConfig {
+                mutation_strength: -1.0,
+                ..config
+            },
+        ] {
+            assert!(bad.validate().is_err());
+            assert!(run_genetic_algo(Checkpoint::new(bad, 7), &out, None).is_err());
+        }
+    }
+
+    #[cfg(feature = "tuner")]
+    #[test]
+    fn genetic_resume() {
+        use super::genetic::*;
+
+        let config = GeneticConfig {
+            population: 4,
+            generations: 3,
+            crossovers: 2,
+            mutations: 2,
+            tournament: 3,
+            mutation_strength: 20.0,
+            fitness: tuner_fitness(),
+        };
+        let dir = std::env::temp_dir().join(format!("tetrizz-checkpoints-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("best.json");
+        let whole = run_genetic_algo(Checkpoint::new(config, 3), &out, None).unwrap();
+
+        // stop after one generation, then pick it back up from the checkpoint
+        let first = GeneticConfig {
+            generations: 1,
+            ..config
+        };
+        run_genetic_algo(Checkpoint::new(first, 3), &out, Some(&dir)).unwrap();
+        let mut state = Checkpoint::latest(&dir).unwrap();
+        assert_eq!(state.generation, 1);
+        state.config.generations = 3;
+        let resumed = run_genetic_algo(state, &out, Some(&dir)).unwrap();
+        assert_eq!(resumed.weights, whole.weights);
+        assert_eq!(resumed.fitness, whole.fitness);
+
+        // every generation got saved, and latest picks the highest one
+        let latest = Checkpoint::latest(&dir).unwrap();
+        assert_eq!(latest.generation, 3);
+        let saved = Checkpoint::load(Checkpoint::path(&dir, 2)).unwrap();
+        assert_eq!(saved.generation, 2);
+        assert_eq!(latest.best_agent.weights, whole.weights);
+        assert_eq!(latest.agents.len(), config.population);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(Checkpoint::latest(&dir).is_err());
+    }
+
+    #[cfg(feature = "tuner")]
+    #[test]
+    fn spsa_steps_uphill() {
+        use super::spsa::*;
+
+        // fitness is how well the weights point at a target, which doesn't care about scale so
+        // normalizing between iterations doesn't change it
+        let target: [f32; 14] = std::array::from_fn(|i| i as f32 - 6.5);
+        let fitness = |w: &[f32; 14]| {
+            let dot: f32 = w.iter().zip(&target).map(|(a, b)| a * b).sum();
+            let norm = |v: &[f32; 14]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+            1000.0 * dot / (norm(w) * norm(&target))
+        };
+        let start = super::genetic::normalized([1.0; 14]);
+        let config = SpsaConfig {
+            iterations: 1,
+            a: 20.0,
+            ..SpsaConfig::default()
+        };
+        for seed in 0..10 {
+            let stepped = spsa(&config, Some(start), seed, fitness, |_| Ok(())).unwrap();
+            assert!(fitness(&stepped) > fitness(&start));
+        }
+        // and keeps going that way with the default step sizes, starting from weights at right
+        // angles to the target
+        let config = SpsaConfig {
+            iterations: 50,
+            ..SpsaConfig::default()
+        };
+        let end = spsa(&config, Some(start), 0, fitness, |_| Ok(())).unwrap();
+        assert!(fitness(&start).abs() < 1.0);
+        assert!(fitness(&end) > 800.0);
+
+        // the real thing comes out the same for the same seed
+        let config = SpsaConfig {
+            iterations: 1,
+            fitness: tuner_fitness(),
+            ..SpsaConfig::default()
+        };
+        let out = std::env::temp_dir().join(format!("tetrizz-spsa-{}.json", std::process::id()));
+        let weights = run_spsa(&config, None, 5, &out).unwrap();
+        assert_eq!(weights, run_spsa(&config, None, 5, &out).unwrap());
+        assert_eq!(Eval::load(&out).unwrap().weights(), weights);
+        std::fs::remove_file(&out).unwrap();
+    }
 }
@@ -1,128 +1,1075 @@
+use tetrizz::analyze::*;
 use tetrizz::beam_search::*;
 use tetrizz::data::*;
 use tetrizz::eval::Eval;
+use tetrizz::image::*;
+use tetrizz::import::*;
+use tetrizz::movegen_cache::CacheStats;
+use tetrizz::render::*;
+use tetrizz::replay::*;
+use tetrizz::simulate::*;
+use tetrizz::versus::*;
 
-use rand::seq::SliceRandom;
-
-fn gen_queue(bags: u32) -> (Piece, Vec<Piece>) {
-    let mut rng = rand::rng();
-    let bag = [
-        Piece::I,
-        Piece::J,
-        Piece::L,
-        Piece::O,
-        Piece::S,
-        Piece::T,
-        Piece::Z,
-    ];
-    let mut queue: Vec<Piece> = vec![];
-    for _ in 0..bags {
-        let mut new_bag = bag.to_vec();
-        new_bag.shuffle(&mut rng);
-        queue.extend(new_bag);
-    }
-    (queue.remove(0), queue)
-}
-
-fn append_queue(queue: &mut Vec<Piece>, bags: u32) {
-    let mut rng = rand::rng();
-    let bag = [
-        Piece::I,
-        Piece::J,
-        Piece::L,
-        Piece::O,
-        Piece::S,
-        Piece::T,
-        Piece::Z,
-    ];
-    for _ in 0..bags {
-        let mut new_bag = bag.to_vec();
-        new_bag.shuffle(&mut rng);
-        queue.extend(new_bag);
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::de::DeserializeOwned;
+
+use std::error::Error;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Instant;
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(name = "tetrizz", about = "tetris bot")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Let the bot play a single game, printing the board every move
+    Play(PlayArgs),
+    /// Play a batch of seeded games headlessly and summarize them
+    Simulate(SimulateArgs),
+    /// Round robin between weight files, rated with elo
+    Arena(ArenaArgs),
+    /// Measure search throughput on a fixed position
+    Bench(BenchArgs),
+    /// Search a single position and list the best first moves
+    Analyze(AnalyzeArgs),
+    /// Step through a recorded game
+    Replay(ReplayArgs),
+    /// Draw placements from a recorded game as an svg or png
+    Export(ExportArgs),
+    /// Compare every move of a TETR.IO or Jstris replay with what the bot would have played
+    Import(ImportArgs),
+    /// Play by hand in the terminal, with the bot giving hints and grading moves
+    #[cfg(feature = "tui")]
+    Practice(PracticeArgs),
+    /// Tune eval weights
+    #[cfg(feature = "tuner")]
+    Tune(TuneArgs),
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Table,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PlayFormat {
+    Board,
+    Json,
+}
+
+// render::Style as a flag, so the library doesn't need clap
+#[derive(Clone, Copy, ValueEnum)]
+enum StyleArg {
+    Ascii,
+    Ansi,
+    Emoji,
+}
+
+impl From<StyleArg> for Style {
+    fn from(style: StyleArg) -> Self {
+        match style {
+            StyleArg::Ascii => Style::Ascii,
+            StyleArg::Ansi => Style::Ansi,
+            StyleArg::Emoji => Style::Emoji,
+        }
+    }
+}
+
+#[derive(Args)]
+struct WeightsArgs {
+    /// Weights file, defaults to the built in weights
+    #[arg(long)]
+    weights: Option<PathBuf>,
+}
+
+impl WeightsArgs {
+    fn eval(&self) -> Result<Eval> {
+        match &self.weights {
+            Some(path) => Eval::load(path)
+                .map_err(|e| format!("couldn't load {}: {e}", path.display()).into()),
+            None => Ok(Eval::default()),
+        }
     }
 }
 
+#[derive(Args)]
+struct SearchArgs {
+    /// Search config file, the flags below override single fields of it
+    #[arg(long)]
+    search: Option<PathBuf>,
+    #[arg(long)]
+    depth: Option<usize>,
+    #[arg(long)]
+    width: Option<usize>,
+    /// How many pieces after the current one the bot can see
+    #[arg(long)]
+    preview: Option<usize>,
+    /// Seed for the eval noise
+    #[arg(long)]
+    noise_seed: Option<u64>,
+    /// Most nodes from the same first move kept in the beam each layer
+    #[arg(long)]
+    root_quota: Option<usize>,
+    /// Keep movegen results for this many boards during a search
+    #[arg(long)]
+    movegen_cache: Option<usize>,
+}
+
+impl SearchArgs {
+    fn config(&self, base: SearchConfig) -> Result<SearchConfig> {
+        let mut config = match &self.search {
+            Some(path) => load_json(path)?,
+            None => base,
+        };
+        config.depth = self.depth.unwrap_or(config.depth);
+        config.width = self.width.unwrap_or(config.width);
+        config.preview = self.preview.unwrap_or(config.preview);
+        config.seed = self.noise_seed.or(config.seed);
+        config.root_quota = self.root_quota.or(config.root_quota);
+        config.movegen_cache = self.movegen_cache.or(config.movegen_cache);
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+#[derive(Args)]
+struct PlayArgs {
+    #[command(flatten)]
+    weights: WeightsArgs,
+    #[command(flatten)]
+    search: SearchArgs,
+    /// Seed for the piece sequence, random if not given
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Stop after this many pieces
+    #[arg(long, default_value_t = 20000)]
+    pieces: usize,
+    /// The game is over once the stack gets above this height
+    #[arg(long, default_value_t = 18)]
+    top_out_height: u8,
+    #[arg(long, value_enum, default_value_t = PlayFormat::Board)]
+    format: PlayFormat,
+    #[arg(long, value_enum, default_value_t = StyleArg::Emoji)]
+    style: StyleArg,
+    /// Where to write the replay, defaults to replays/<seed>.json
+    #[arg(long)]
+    record: Option<PathBuf>,
+    /// Don't write a replay
+    #[arg(long, conflicts_with = "record")]
+    no_record: bool,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    replay: PathBuf,
+    /// Move to start at
+    #[arg(long, default_value_t = 0)]
+    at: usize,
+    #[arg(long, value_enum, default_value_t = StyleArg::Ascii)]
+    style: StyleArg,
+}
+
+#[derive(Args)]
+struct SimulateArgs {
+    /// Simulation config file, the flags below override single fields of it
+    #[arg(long)]
+    config: Option<PathBuf>,
+    #[command(flatten)]
+    weights: WeightsArgs,
+    #[command(flatten)]
+    search: SearchArgs,
+    #[arg(long)]
+    games: Option<usize>,
+    /// Pieces per game
+    #[arg(long)]
+    pieces: Option<usize>,
+    /// Game i is seeded with seed + i
+    #[arg(long)]
+    seed: Option<u64>,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+}
+
+#[derive(Args)]
+struct ArenaArgs {
+    /// Weight files to play against each other
+    #[arg(required = true, num_args = 2..)]
+    bots: Vec<PathBuf>,
+    #[command(flatten)]
+    search: SearchArgs,
+    /// Games between every pair of bots
+    #[arg(long)]
+    games: Option<usize>,
+    #[arg(long)]
+    max_pieces: Option<usize>,
+    #[arg(long)]
+    garbage_cap: Option<u32>,
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Resamples for the confidence intervals
+    #[arg(long)]
+    bootstrap: Option<usize>,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+}
+
+#[derive(Args)]
+struct BenchArgs {
+    #[command(flatten)]
+    weights: WeightsArgs,
+    #[command(flatten)]
+    search: SearchArgs,
+    /// Number of searches to time
+    #[arg(long, default_value_t = 10)]
+    iterations: usize,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+}
+
+#[derive(Args)]
+struct AnalyzeArgs {
+    /// Board as rows of 10 cells from the top down, split by '/' or newlines ('.' is empty), or
+    /// a v115 fumen
+    #[arg(long, conflicts_with = "board_file")]
+    board: Option<String>,
+    /// File holding the board, in the same formats as --board
+    #[arg(long)]
+    board_file: Option<PathBuf>,
+    /// Current piece followed by the preview, like TSZLJ
+    #[arg(long)]
+    queue: String,
+    #[arg(long)]
+    hold: String,
+    #[arg(long, default_value_t = 0)]
+    b2b: u64,
+    #[arg(long, default_value_t = 0)]
+    combo: u32,
+    /// How many first moves to list
+    #[arg(long, default_value_t = 5)]
+    top: usize,
+    #[command(flatten)]
+    weights: WeightsArgs,
+    #[command(flatten)]
+    search: SearchArgs,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+    #[arg(long, value_enum, default_value_t = StyleArg::Ascii)]
+    style: StyleArg,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Client {
+    Tetrio,
+    Jstris,
+}
+
+#[derive(Args)]
+struct ImportArgs {
+    /// A .ttr or .ttrm replay saved from TETR.IO, or a Jstris replay as shared or as json
+    replay: PathBuf,
+    /// Where the replay is from, TETR.IO for .ttr and .ttrm files and Jstris otherwise
+    #[arg(long, value_enum)]
+    from: Option<Client>,
+    /// Round of a .ttrm to import
+    #[arg(long, default_value_t = 0)]
+    round: usize,
+    /// Whose game to import from a .ttrm, in the order the replay lists the players
+    #[arg(long, default_value_t = 0)]
+    player: usize,
+    #[command(flatten)]
+    weights: WeightsArgs,
+    #[command(flatten)]
+    search: SearchArgs,
+    #[arg(long, value_enum, default_value_t = Format::Table)]
+    format: Format,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    replay: PathBuf,
+    /// Image to write, .svg or .png. png has no font, so titles, the hold and next labels and
+    /// notes only show up in svg
+    #[arg(long, short)]
+    out: PathBuf,
+    /// First move to draw
+    #[arg(long, default_value_t = 0)]
+    at: usize,
+    /// How many consecutive placements to draw side by side
+    #[arg(long, default_value_t = 1)]
+    count: usize,
+    /// Queue pieces shown next to each board, after the current one
+    #[arg(long, default_value_t = 5)]
+    preview: usize,
+    /// Size of a cell in pixels
+    #[arg(long, default_value_t = 24)]
+    cell: u32,
+}
+
+fn load_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {e}", path.display()))?;
+    serde_json::from_str(&text)
+        .map_err(|e| format!("couldn't parse {}: {e}", path.display()).into())
+}
+
 fn main() {
-    let (test_hold, mut test_queue) = gen_queue(50);
-    let eval = Eval::new(
-        -79.400375,
-        -55.564907,
-        -125.680145,
-        -170.41902,
-        10.167948,
-        -172.78625,
-        -478.7291,
-        86.84883,
-        368.89203,
-        272.57874,
-        28.938646,
-        -104.59018,
-        -496.8832,
-        458.29822,
+    let cli = Cli::parse();
+    let result = match cli.command {
+        Command::Play(args) => play(args),
+        Command::Simulate(args) => simulate_cmd(args),
+        Command::Arena(args) => arena(args),
+        Command::Bench(args) => bench(args),
+        Command::Analyze(args) => analyze_cmd(args),
+        Command::Replay(args) => replay_cmd(args),
+        Command::Export(args) => export(args),
+        Command::Import(args) => import_cmd(args),
+        #[cfg(feature = "tui")]
+        Command::Practice(args) => tui::practice(args),
+        #[cfg(feature = "tuner")]
+        Command::Tune(args) => tune::tune(args),
+    };
+    if let Err(e) = result {
+        eprintln!("error: {e}");
+        exit(1);
+    }
+}
+
+fn play(args: PlayArgs) -> Result<()> {
+    let eval = args.weights.eval()?;
+    let config = args.search.config(SearchConfig::default())?;
+    let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+    let mut rng = StdRng::seed_from_u64(seed);
+    let record = match (&args.record, args.no_record) {
+        (_, true) => None,
+        (Some(path), false) => Some(path.clone()),
+        (None, false) => Some(PathBuf::from(format!("replays/{seed}.json"))),
+    };
+
+    let (hold, mut queue) = gen_queue(50, &mut rng);
+    let mut game = Game::new(Some(hold));
+    let mut replay = Replay::new(seed, eval, config, hold);
+    replay.queue.clone_from(&queue);
+
+    for p in 0..args.pieces {
+        if queue.len() < 50 {
+            let more = gen_bags(10, &mut rng);
+            replay.queue.extend(&more);
+            queue.extend(more);
+        }
+        let (loc, stats) = match search_with_stats(&game, &queue, &eval, &config) {
+            Ok(found) => found,
+            Err(e) => {
+                // json output stays one object per line all the way to the end
+                match args.format {
+                    PlayFormat::Board => println!("game over after {p} pieces: {e}"),
+                    PlayFormat::Json => println!(
+                        "{}",
+                        serde_json::json!({ "game_over": p, "error": e.to_string() })
+                    ),
+                }
+                break;
+            }
+        };
+
+        match args.format {
+            PlayFormat::Board => print_game(&game, &queue, loc, p, args.style.into()),
+            PlayFormat::Json => println!(
+                "{}",
+                serde_json::json!({ "move": p, "current": queue[0], "hold": game.hold(), "location": loc })
+            ),
+        }
+
+        let info = game.advance(queue[0], loc);
+        replay.moves.push(Move {
+            piece: queue[0],
+            loc,
+            info,
+            stats,
+        });
+        queue.remove(0);
+        // save as we go so stopping a long game early still leaves a replay
+        if let Some(path) = &record {
+            if (p + 1) % 100 == 0 {
+                replay.save(path)?;
+            }
+        }
+        if game.board.max_height() > args.top_out_height {
+            break;
+        }
+    }
+    if let Some(path) = &record {
+        replay.save(path)?;
+        eprintln!("replay written to {}", path.display());
+    }
+    Ok(())
+}
+
+fn print_game(game: &Game, queue: &[Piece], loc: PieceLocation, p: usize, style: Style) {
+    let view = View::new(&game.board)
+        .piece(loc)
+        .hold(game.hold())
+        .queue(&queue[1..queue.len().min(6)])
+        .height(20)
+        .note("")
+        .note(format!(
+            "b2b              ⭐️ \x1b[1m{}\x1b[0m ⭐️",
+            game.b2b()
+        ))
+        .note(format!("pieces placed    {}", p + 1))
+        .note(format!(
+            "p/b2b            {:?}",
+            ((p + 1) as f64) / (game.b2b() as f64)
+        ))
+        .note(format!("placed piece     {:?}", loc.piece));
+    println!("\n\n\n\n\n\n\n\n{}", view.render(style));
+}
+
+fn simulate_cmd(args: SimulateArgs) -> Result<()> {
+    let eval = args.weights.eval()?;
+    let mut config: SimConfig = match &args.config {
+        Some(path) => load_json(path)?,
+        None => SimConfig::default(),
+    };
+    config.search = args.search.config(config.search)?;
+    config.games = args.games.unwrap_or(config.games);
+    config.pieces = args.pieces.unwrap_or(config.pieces);
+    config.seed = args.seed.unwrap_or(config.seed);
+
+    let summary = simulate(&eval, &config);
+    match args.format {
+        Format::Table => println!("{summary}"),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&summary)?),
+    }
+    Ok(())
+}
+
+fn arena(args: ArenaArgs) -> Result<()> {
+    let search = args.search.config(SearchConfig::default())?;
+    let defaults = VersusConfig::default();
+    let config = VersusConfig {
+        games: args.games.unwrap_or(defaults.games),
+        max_pieces: args.max_pieces.unwrap_or(defaults.max_pieces),
+        garbage_cap: args.garbage_cap.unwrap_or(defaults.garbage_cap),
+        seed: args.seed.unwrap_or(defaults.seed),
+        bootstrap: args.bootstrap.unwrap_or(defaults.bootstrap),
+    };
+
+    let bots = args
+        .bots
+        .iter()
+        .map(|path| {
+            Ok(Bot {
+                name: path.file_stem().map_or(path.display().to_string(), |s| {
+                    s.to_string_lossy().into_owned()
+                }),
+                eval: Eval::load(path)
+                    .map_err(|e| format!("couldn't load {}: {e}", path.display()))?,
+                search,
+            })
+        })
+        .collect::<Result<Vec<Bot>>>()?;
+
+    let games = round_robin(&bots, &config);
+    let ratings = ratings(&bots, &games, &config);
+
+    match args.format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&ratings)?),
+        Format::Table => {
+            println!(
+                "{:<24} {:>8} {:>18} {:>6} {:>6} {:>6}",
+                "bot", "elo", "95% ci", "won", "lost", "drawn"
+            );
+            for r in &ratings {
+                println!(
+                    "{:<24} {:>8.1} {:>18} {:>6} {:>6} {:>6}",
+                    r.name,
+                    r.elo,
+                    format!("[{:.1}, {:.1}]", r.low, r.high),
+                    r.wins,
+                    r.losses,
+                    r.draws
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn bench(args: BenchArgs) -> Result<()> {
+    let eval = args.weights.eval()?;
+    let config = args.search.config(SearchConfig::default())?;
+    let (hold, queue) = gen_queue(4, &mut StdRng::seed_from_u64(0));
+    let game = bench_game(hold);
+
+    let search_loc = candidates(&game, queue[0], &config);
+    let mut nodes = 0;
+    let mut cache = CacheStats::default();
+    let start = Instant::now();
+    for _ in 0..args.iterations {
+        let stats = search_results(&game, &search_loc, &queue, &eval, &config)?.stats;
+        nodes += stats.nodes;
+        cache.hits += stats.cache.hits;
+        cache.misses += stats.cache.misses;
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    let per_search = seconds / args.iterations.max(1) as f64;
+    let nps = nodes as f64 / seconds;
+
+    match args.format {
+        Format::Table => {
+            println!("searches        {}", args.iterations);
+            println!("ms per search   {:.2}", per_search * 1000.0);
+            println!("nodes per sec   {nps:.0}");
+            if config.movegen_cache.is_some() {
+                println!("cache hit rate  {:.1}%", cache.hit_rate() * 100.0);
+            }
+        }
+        Format::Json => println!(
+            "{}",
+            serde_json::json!({ "searches": args.iterations, "seconds_per_search": per_search, "nodes_per_second": nps, "cache_hit_rate": config.movegen_cache.map(|_| cache.hit_rate()) })
+        ),
+    }
+    Ok(())
+}
+
+fn analyze_cmd(args: AnalyzeArgs) -> Result<()> {
+    let eval = args.weights.eval()?;
+    let config = args.search.config(SearchConfig::default())?;
+    let board = match (&args.board, &args.board_file) {
+        (Some(board), _) => parse_board(board)?,
+        (None, Some(path)) => parse_board(
+            &fs::read_to_string(path)
+                .map_err(|e| format!("couldn't read {}: {e}", path.display()))?,
+        )?,
+        (None, None) => parse_board("")?,
+    };
+    let queue = parse_queue(&args.queue)?;
+    let mut game = Game::new(Some(args.hold.parse()?));
+    game.board = board;
+    game.set_b2b(args.b2b);
+    game.set_combo(args.combo);
+
+    let analysis = analyze(&game, &queue, &eval, &config, args.top)?;
+    match args.format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&analysis)?),
+        Format::Table => {
+            println!(
+                "{} nodes, {} pieces deep\n",
+                analysis.stats.nodes, analysis.stats.depth
+            );
+            for (i, c) in analysis.candidates.iter().enumerate() {
+                println!(
+                    "#{}  {}  score {:.2}  reward {:.2}  value {:.2}  lines {}  attack {}",
+                    i + 1,
+                    describe(c.loc),
+                    c.score,
+                    c.reward,
+                    c.value,
+                    c.info.lines_cleared,
+                    c.info.attack
+                );
+                let line: Vec<String> = c.line[1..].iter().map(|(l, _)| describe(*l)).collect();
+                println!("    then {}", line.join(", "));
+                // the board before the placement clears anything, with the piece marked on it
+                println!(
+                    "{}\n",
+                    View::new(&game.board)
+                        .piece(c.loc)
+                        .render(args.style.into())
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+fn replay_cmd(args: ReplayArgs) -> Result<()> {
+    let replay = Replay::load(&args.replay)
+        .map_err(|e| format!("couldn't load {}: {e}", args.replay.display()))?;
+    let mut player = Player::new(&replay)?;
+    player.seek(args.at.min(player.len()));
+
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print_replay(&player, args.style.into());
+        print!("[enter] next  [b] back  [<move>] jump  [f] fumen  [q] quit > ");
+        io::stdout().flush()?;
+        let Some(line) = lines.next() else {
+            break;
+        };
+        match line?.trim() {
+            "" | "n" => {
+                player.forward();
+            }
+            "b" | "p" => {
+                player.back();
+            }
+            "f" => {
+                let board = ColoredBoard {
+                    board: player.game().board,
+                    colors: player.colors().clone(),
+                };
+                println!("{}", tetrizz::fumen::encode(&board));
+            }
+            "q" => break,
+            n => match n.parse() {
+                Ok(n) if player.seek(n) => {}
+                _ => println!("no move {n:?}, there are {}", player.len()),
+            },
+        }
+    }
+    Ok(())
+}
+
+fn export(args: ExportArgs) -> Result<()> {
+    let replay = Replay::load(&args.replay)
+        .map_err(|e| format!("couldn't load {}: {e}", args.replay.display()))?;
+    if args.at > replay.moves.len() {
+        return Err(format!("no move {}, there are {}", args.at, replay.moves.len()).into());
+    }
+    let filmstrip = Filmstrip::new(&replay, args.at, args.count.max(1), args.preview)?;
+    let views = filmstrip.views();
+    let image = match args.out.extension().and_then(|e| e.to_str()) {
+        Some("svg") => svg(&views, args.cell).into_bytes(),
+        #[cfg(feature = "png")]
+        Some("png") => {
+            if has_text(&views) {
+                eprintln!("warning: png has no font, titles, labels and notes are left out");
+            }
+            png(&views, args.cell)?
+        }
+        #[cfg(not(feature = "png"))]
+        Some("png") => return Err("png export needs the png feature".into()),
+        _ => return Err("the output has to end in .svg or .png".into()),
+    };
+    fs::write(&args.out, image)
+        .map_err(|e| format!("couldn't write {}: {e}", args.out.display()))?;
+    Ok(())
+}
+
+fn print_replay(player: &Player, style: Style) {
+    let game = player.game();
+    println!("\nmove {}/{}", player.position(), player.len());
+    if let Some(m) = player.last_move() {
+        println!(
+            "last: {}   lines {}   attack {}   searched {} nodes, {} deep",
+            describe(m.loc),
+            m.info.lines_cleared,
+            m.info.attack,
+            m.stats.nodes,
+            m.stats.depth
+        );
+    }
+    let queue = player.queue();
+    let mut view = View::new(&game.board)
+        .hold(game.hold())
+        .queue(&queue[..queue.len().min(6)])
+        .note(format!("b2b   {}", game.b2b()))
+        .note(format!("combo {}", game.combo()));
+    match player.next_move() {
+        Some(m) => {
+            println!("next: {}", describe(m.loc));
+            view = view.piece(m.loc);
+        }
+        None => println!("end of game"),
+    }
+    println!("{}", view.render(style));
+}
+
+fn import_cmd(args: ImportArgs) -> Result<()> {
+    let eval = args.weights.eval()?;
+    let config = args.search.config(SearchConfig::default())?;
+    let text = fs::read_to_string(&args.replay)
+        .map_err(|e| format!("couldn't read {}: {e}", args.replay.display()))?;
+    let tetrio = matches!(
+        args.replay.extension().and_then(|e| e.to_str()),
+        Some("ttr" | "ttrm")
     );
-    let mut game = Game::new(Some(test_hold));
-
-    let mut all_locations: Vec<PieceLocation> = vec![];
-
-    for p in 0..20000 {
-        if test_queue.len() < 50 {
-            append_queue(&mut test_queue, 10);
-        }
-        let loc = search(&game, test_queue.clone(), &eval, 7, 30000);
-        all_locations.push(loc);
-
-        let mut outstr: Vec<String> = vec![];
-        for y in (0..20).rev() {
-            let mut vstr = String::new();
-            for x in 0..10 {
-                vstr.push_str(if (game.board.cols[x as usize].0 & 1 << y) > 0 {
-                    "🟩"
-                } else if loc.blocks().iter().any(|(bx, by)| *bx == x && *by == y) {
-                    if loc.spun {
-                        "🟨"
-                    } else {
-                        "🟥"
-                    }
-                } else {
-                    "⬜️"
+    let game = match args.from {
+        Some(Client::Tetrio) => tetrizz::tetrio::import(&text, args.round, args.player),
+        None if tetrio => tetrizz::tetrio::import(&text, args.round, args.player),
+        Some(Client::Jstris) | None => tetrizz::jstris::import(&text),
+    }
+    .map_err(|e| format!("couldn't import {}: {e}", args.replay.display()))?;
+
+    let mut comparisons = vec![];
+    if let Format::Table = args.format {
+        println!("{} moves\n", game.moves.len());
+        println!(
+            "{:>5}  {:<28} {:<28} {:>8}",
+            "move", "played", "bot", "delta"
+        );
+    }
+    for (i, c) in review(&game, &eval, &config)?.enumerate() {
+        if let Format::Table = args.format {
+            let (bot, delta) = match (&c.bot, c.delta()) {
+                (_, _) if c.agrees() => ("=".to_string(), String::new()),
+                (Some((bot, _)), Some(delta)) => (describe(*bot), format!("{delta:.2}")),
+                _ => ("no move".to_string(), String::new()),
+            };
+            println!(
+                "{:>5}  {:<28} {:<28} {:>8}",
+                i + 1,
+                describe(c.human),
+                bot,
+                delta
+            );
+        }
+        comparisons.push(c);
+    }
+
+    match args.format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(&comparisons)?),
+        Format::Table => {
+            let agreed = comparisons.iter().filter(|c| c.agrees()).count();
+            let deltas: Vec<(usize, f32)> = comparisons
+                .iter()
+                .enumerate()
+                .filter_map(|(i, c)| Some((i + 1, c.delta()?)))
+                .collect();
+            println!(
+                "\nagreed with the bot on {agreed} of {} moves",
+                comparisons.len()
+            );
+            if !deltas.is_empty() {
+                let mean = deltas.iter().map(|&(_, d)| d).sum::<f32>() / deltas.len() as f32;
+                let (worst, delta) = deltas.iter().fold(deltas[0], |a, &b| match b.1 < a.1 {
+                    true => b,
+                    false => a,
                 });
+                println!("average delta {mean:.2}, worst move {worst} ({delta:.2})");
             }
-            outstr.push(vstr);
         }
+    }
+    Ok(())
+}
+
+fn describe(loc: PieceLocation) -> String {
+    let spin = if loc.spun { " spin" } else { "" };
+    format!(
+        "{:?} {:?} x={} y={}{spin}",
+        loc.piece, loc.rotation, loc.x, loc.y
+    )
+}
+
+#[cfg(feature = "tuner")]
+mod tune {
+    use super::*;
+    use tetrizz::genetic::*;
+    use tetrizz::spsa::*;
+
+    #[derive(Args)]
+    pub struct TuneArgs {
+        #[command(subcommand)]
+        method: Method,
+    }
+
+    #[derive(Subcommand)]
+    enum Method {
+        /// Genetic algorithm, can checkpoint and resume
+        Genetic(GeneticArgs),
+        /// Simultaneous perturbation stochastic approximation
+        Spsa(SpsaArgs),
+    }
+
+    #[derive(Args)]
+    struct GeneticArgs {
+        /// Tuner config file, the flags below override single fields of it
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Where the best weights so far get written
+        #[arg(long, default_value = "best_weights.json")]
+        out: PathBuf,
+        #[arg(long)]
+        population: Option<usize>,
+        #[arg(long)]
+        generations: Option<usize>,
+        #[arg(long)]
+        crossovers: Option<usize>,
+        #[arg(long)]
+        mutations: Option<usize>,
+        #[arg(long)]
+        tournament: Option<usize>,
+        #[arg(long)]
+        mutation_strength: Option<f32>,
+        /// Seed for the tuner itself, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Directory to write a checkpoint to after every generation
+        #[arg(long)]
+        checkpoints: Option<PathBuf>,
+        /// Continue from the latest checkpoint in this directory. Only --out and --generations
+        /// can be changed on a resumed run
+        #[arg(long, conflicts_with_all = ["config", "population", "crossovers", "mutations",
+            "tournament", "mutation_strength", "seed", "checkpoints"])]
+        resume: Option<PathBuf>,
+    }
+
+    #[derive(Args)]
+    struct SpsaArgs {
+        /// Tuner config file, the flags below override single fields of it
+        #[arg(long)]
+        config: Option<PathBuf>,
+        /// Where the current weights get written
+        #[arg(long, default_value = "best_weights.json")]
+        out: PathBuf,
+        #[arg(long)]
+        iterations: Option<usize>,
+        /// Weights file to start from, random if not given
+        #[arg(long)]
+        start: Option<PathBuf>,
+        /// Seed for the tuner itself, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+    }
+
+    pub fn tune(args: TuneArgs) -> Result<()> {
+        match args.method {
+            Method::Genetic(args) => genetic(args),
+            Method::Spsa(args) => spsa(args),
+        }
+    }
+
+    fn genetic(args: GeneticArgs) -> Result<()> {
+        let (state, checkpoints) = match args.resume {
+            Some(dir) => {
+                let mut state = Checkpoint::latest(&dir)
+                    .map_err(|e| format!("couldn't resume from {}: {e}", dir.display()))?;
+                println!("resuming from generation {}", state.generation);
+                state.config.generations = args.generations.unwrap_or(state.config.generations);
+                (state, Some(dir))
+            }
+            None => {
+                let mut config: GeneticConfig = match &args.config {
+                    Some(path) => load_json(path)?,
+                    None => GeneticConfig::default(),
+                };
+                config.population = args.population.unwrap_or(config.population);
+                config.generations = args.generations.unwrap_or(config.generations);
+                config.crossovers = args.crossovers.unwrap_or(config.crossovers);
+                config.mutations = args.mutations.unwrap_or(config.mutations);
+                config.tournament = args.tournament.unwrap_or(config.tournament);
+                config.mutation_strength =
+                    args.mutation_strength.unwrap_or(config.mutation_strength);
+                let seed = args.seed.unwrap_or_else(rand::random);
+                (Checkpoint::new(config, seed), args.checkpoints)
+            }
+        };
+
+        state.config.validate()?;
+        let best = run_genetic_algo(state, &args.out, checkpoints.as_deref())
+            .map_err(|e| format!("couldn't write {}: {e}", args.out.display()))?;
+        println!("best weights written to {}: {best:?}", args.out.display());
+        Ok(())
+    }
 
-        let mut queue5 = test_queue.clone();
-        queue5.truncate(5);
+    fn spsa(args: SpsaArgs) -> Result<()> {
+        let mut config: SpsaConfig = match &args.config {
+            Some(path) => load_json(path)?,
+            None => SpsaConfig::default(),
+        };
+        config.iterations = args.iterations.unwrap_or(config.iterations);
+        let start = match &args.start {
+            Some(path) => Some(
+                Eval::load(path)
+                    .map_err(|e| format!("couldn't load {}: {e}", path.display()))?
+                    .weights(),
+            ),
+            None => None,
+        };
+        config.fitness.sim.search.validate()?;
+        let seed = args.seed.unwrap_or_else(rand::random);
 
-        outstr[5] += &format!("          b2b:            ⭐️ \x1b[1m{}\x1b[0m ⭐️", game.b2b);
-        outstr[6] += &format!("          pieces placed:    {:?}", p + 1);
-        outstr[7] += &format!(
-            "          p/b2b:            {:?}",
-            ((p + 1) as f64) / (game.b2b as f64)
+        let weights = run_spsa(&config, start, seed, &args.out)
+            .map_err(|e| format!("couldn't write {}: {e}", args.out.display()))?;
+        println!(
+            "final weights written to {}: {weights:?}",
+            args.out.display()
         );
-        outstr[8] += &format!("          board:            {:?}", game.board.cols);
-        outstr[9] += &format!("          queue (next 5):   {queue5:?}");
-        outstr[10] += &format!("          hold piece:       {:?}", game.hold);
+        Ok(())
+    }
+}
 
-        outstr[12] += &format!("          placed piece:     {:?}", loc.piece);
+#[cfg(feature = "tuner")]
+use tune::TuneArgs;
 
-        println!("\n\n\n\n\n\n\n\n{}", outstr.join("\n"));
+#[cfg(feature = "tui")]
+mod tui {
+    use super::*;
+    use tetrizz::practice::*;
 
-        game.advance(test_queue[0], loc);
-        if loc.piece == game.hold {
-            game.hold = test_queue[0]
+    use crossterm::cursor::{Hide, MoveTo, Show};
+    use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+    use crossterm::execute;
+    use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+
+    #[derive(Args)]
+    pub struct PracticeArgs {
+        #[command(flatten)]
+        weights: WeightsArgs,
+        #[command(flatten)]
+        search: SearchArgs,
+        /// Seed for the piece sequence, random if not given
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long, value_enum, default_value_t = StyleArg::Ansi)]
+        style: StyleArg,
+    }
+
+    const KEYS: &str = "arrows move, up/x cw, z ccw, a 180, space drop, c hold, h hint, q quit";
+
+    pub fn practice(args: PracticeArgs) -> Result<()> {
+        let eval = args.weights.eval()?;
+        let search = args.search.config(SearchConfig::default())?;
+        let seed = args.seed.unwrap_or_else(|| rand::rng().random());
+        let mut session = Practice::new(seed, eval, search);
+
+        let mut stdout = io::stdout();
+        terminal::enable_raw_mode()?;
+        execute!(stdout, EnterAlternateScreen, Hide)?;
+        let result = run(&mut session, args.style.into());
+        execute!(stdout, Show, LeaveAlternateScreen)?;
+        terminal::disable_raw_mode()?;
+        result?;
+        println!("placed {} pieces, seed {seed}", session.pieces);
+        Ok(())
+    }
+
+    fn run(session: &mut Practice, style: Style) -> Result<()> {
+        let mut hint = None;
+        let mut last: Option<Comparison> = None;
+        let mut message = String::new();
+        loop {
+            draw(session, hint, last.as_ref(), &message, style)?;
+            if session.over {
+                event::read()?;
+                return Ok(());
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            message.clear();
+            let input = match key.code {
+                KeyCode::Left => Input::Left,
+                KeyCode::Right => Input::Right,
+                KeyCode::Down => Input::SoftDrop,
+                KeyCode::Char(' ') => Input::HardDrop,
+                KeyCode::Up | KeyCode::Char('x') => Input::RotateCw,
+                KeyCode::Char('z') => Input::RotateCcw,
+                KeyCode::Char('a') => Input::Rotate180,
+                KeyCode::Char('c') => Input::Hold,
+                KeyCode::Char('h') => {
+                    match session.hint() {
+                        Ok(loc) => hint = Some(loc),
+                        Err(e) => message = format!("no hint: {e}"),
+                    }
+                    continue;
+                }
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                _ => continue,
+            };
+            if let Some(comparison) = session.input(input) {
+                last = Some(comparison);
+                hint = None;
+            }
+        }
+    }
+
+    fn draw(
+        session: &Practice,
+        hint: Option<PieceLocation>,
+        last: Option<&Comparison>,
+        message: &str,
+        style: Style,
+    ) -> Result<()> {
+        let game = &session.game;
+        let queue = &session.queue;
+        // after a hold the current piece is the one sitting in hold
+        let hold = match session.active.piece == queue[0] {
+            true => game.hold(),
+            false => queue[0],
+        };
+        let mut view = View::new(&game.board)
+            .piece(session.active)
+            .ghost(true)
+            .hold(hold)
+            .queue(&queue[1..queue.len().min(6)])
+            .height(22)
+            .note("")
+            .note(format!("pieces {}", session.pieces))
+            .note(format!("b2b    {}", game.b2b()))
+            .note(format!("combo  {}", game.combo()));
+        if let Some(hint) = hint {
+            view = view.hint(hint);
         }
 
-        test_queue.remove(0);
-        if game
-            .board
-            .cols
-            .into_iter()
-            .map(Column::height)
-            .max()
-            .unwrap()
-            > 18
-        {
-            break;
+        let mut out = vec![view.render(style), String::new()];
+        if let Some(c) = last {
+            out.push(grade(c));
+        }
+        if session.over {
+            out.push("topped out, press any key".into());
+        } else if !message.is_empty() {
+            out.push(message.into());
+        }
+        out.push(KEYS.into());
+
+        let mut stdout = io::stdout();
+        execute!(stdout, MoveTo(0, 0), Clear(ClearType::All))?;
+        // raw mode doesn't go back to the start of the line on its own
+        print!("{}", out.join("\n").replace('\n', "\r\n"));
+        stdout.flush()?;
+        Ok(())
+    }
+
+    // the last move next to the bot's, term by term
+    fn grade(c: &Comparison) -> String {
+        let Some((bot, bot_terms)) = &c.bot else {
+            return format!("you played {}, the bot had no move", describe(c.human));
+        };
+        let verdict = match c.agrees() {
+            true => "same as the bot".to_string(),
+            false => format!("bot played {}", describe(*bot)),
+        };
+        let mut lines = vec![
+            format!("you played {}, {verdict}", describe(c.human)),
+            format!("{:<20} {:>9} {:>9} {:>9}", "", "you", "bot", "diff"),
+        ];
+        let (mut you_total, mut bot_total) = (0.0, 0.0);
+        for (&(name, you), &(_, bot)) in c.human_terms.iter().zip(bot_terms) {
+            you_total += you;
+            bot_total += bot;
+            if you != 0.0 || bot != 0.0 {
+                lines.push(format!(
+                    "{name:<20} {you:>9.1} {bot:>9.1} {:>9.1}",
+                    you - bot
+                ));
+            }
         }
+        lines.push(format!(
+            "{:<20} {you_total:>9.1} {bot_total:>9.1} {:>9.1}",
+            "total",
+            you_total - bot_total
+        ));
+        lines.join("\n")
     }
 }
+
+#[cfg(feature = "tui")]
+use tui::PracticeArgs;
@@ -1,16 +1,40 @@
 use crate::data::*;
+use crate::render::{side_by_side, Style, View};
+
+use serde::{Deserialize, Serialize};
 use std::fmt;
 
-const FULL_HEIGHT: u64 = (1 << 40) - 1;
+// the walls and floor are solid all the way up, so nothing can be placed outside the board no
+// matter how tall the stack is
+const FULL_HEIGHT: u64 = u64::MAX;
+
+// movegen explores up to 3 rows above the stack, which has to fit in a column. boards taller
+// than this have no placements at all
+pub const MAX_STACK_HEIGHT: u8 = 60;
 
 pub fn movegen(game: &Game, next: Piece) -> Vec<PieceLocation> {
     let mut next_pieces = movegen_piece(&game.board, next);
-    let mut hold_pieces = movegen_piece(&game.board, game.hold);
+    let mut hold_pieces = movegen_piece(&game.board, game.hold());
     next_pieces.append(&mut hold_pieces);
     next_pieces
 }
 
 pub fn movegen_piece(board: &Board, piece: Piece) -> Vec<PieceLocation> {
+    movegen_piece_with(board, piece, true)
+}
+
+// same as movegen_piece, but 180 rotations can be turned off for games that don't have them
+pub fn movegen_piece_with(board: &Board, piece: Piece, allow_180: bool) -> Vec<PieceLocation> {
+    movegen_piece_in(board, piece, RotationSystem::SrsPlus, allow_180)
+}
+
+// movegen for games that kick pieces differently
+pub fn movegen_piece_in(
+    board: &Board,
+    piece: Piece,
+    system: RotationSystem,
+    allow_180: bool,
+) -> Vec<PieceLocation> {
     const ROT: [Rotation; 4] = [
         Rotation::North,
         Rotation::East,
@@ -19,6 +43,10 @@ pub fn movegen_piece(board: &Board, piece: Piece) -> Vec<PieceLocation> {
     ];
     const PAIRS: [[usize; 3]; 4] = [[1, 2, 3], [0, 2, 3], [0, 1, 3], [0, 1, 2]];
 
+    if board.max_height() > MAX_STACK_HEIGHT {
+        return vec![];
+    }
+
     let mut maps = ROT.map(|r| CollisionMap::new(board, piece, r));
 
     if piece != Piece::O {
@@ -35,7 +63,12 @@ pub fn movegen_piece(board: &Board, piece: Piece) -> Vec<PieceLocation> {
                 }
 
                 for i1 in PAIRS[i2] {
-                    let kicks = kicks(piece, ROT[i1], ROT[i2]);
+                    if !allow_180 && i1 == (i2 + 2) % 4 {
+                        continue;
+                    }
+                    let Some(kicks) = system.kicks(piece, ROT[i1], ROT[i2]) else {
+                        continue;
+                    };
                     let mut p1f = maps[i1].explored;
                     for (kx, ky) in kicks {
                         let mut mask = all_valid;
@@ -124,8 +157,8 @@ pub fn movegen_piece(board: &Board, piece: Piece) -> Vec<PieceLocation> {
         Piece::T => {
             let mut s = [0u64; 10];
             for (x, item) in s.iter_mut().enumerate() {
-                let west = board.cols.get(x - 1).map(|c| c.0).unwrap_or(FULL_HEIGHT);
-                let east = board.cols.get(x + 1).map(|c| c.0).unwrap_or(FULL_HEIGHT);
+                let west = board.cols().get(x - 1).map(|c| c.0).unwrap_or(FULL_HEIGHT);
+                let east = board.cols().get(x + 1).map(|c| c.0).unwrap_or(FULL_HEIGHT);
 
                 let c1 = west << 1 | 1;
                 let c2 = east << 1 | 1;
@@ -201,8 +234,83 @@ pub fn movegen_piece(board: &Board, piece: Piece) -> Vec<PieceLocation> {
     positions
 }
 
-const fn kicks(piece: Piece, from: Rotation, to: Rotation) -> [(i8, i8); 6] {
-    match piece {
+// which kick table turns go through
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationSystem {
+    // tetr.io's srs+: srs with mirrored i kicks, plus 180s
+    #[default]
+    SrsPlus,
+    // guideline srs, which has no 180s
+    Srs,
+    // jstris' srs: guideline srs, plus 180s that only try turning in place and then one cell over
+    Jstris,
+}
+
+impl RotationSystem {
+    // None if there's no rotation between from and to in this system
+    pub const fn kicks(self, piece: Piece, from: Rotation, to: Rotation) -> Option<[(i8, i8); 6]> {
+        match self {
+            RotationSystem::SrsPlus => kicks(piece, from, to),
+            RotationSystem::Srs => srs_kicks(piece, from, to),
+            RotationSystem::Jstris => jstris_kicks(piece, from, to),
+        }
+    }
+}
+
+// turns a piece the way a player would, trying each kick in order. None if every kick collides
+pub fn rotate(
+    board: &Board,
+    loc: PieceLocation,
+    to: Rotation,
+    system: RotationSystem,
+) -> Option<PieceLocation> {
+    system
+        .kicks(loc.piece, loc.rotation, to)?
+        .into_iter()
+        .map(|(kx, ky)| PieceLocation {
+            rotation: to,
+            x: loc.x + kx,
+            y: loc.y + ky,
+            ..loc
+        })
+        .find(|l| !board.collides(*l))
+}
+
+// the placement movegen gives for a piece resting on the same cells as loc. that has spun and
+// possible_line_clear filled in, and uses the rotation movegen picks for pieces that look the same
+// in two rotations. None if movegen can't get a piece there
+pub fn find_placement(
+    board: &Board,
+    loc: PieceLocation,
+    system: RotationSystem,
+    allow_180: bool,
+) -> Option<PieceLocation> {
+    let mut cells = loc.blocks();
+    cells.sort();
+    movegen_piece_in(board, loc.piece, system, allow_180)
+        .into_iter()
+        .find(|l| {
+            let mut other = l.blocks();
+            other.sort();
+            other == cells
+        })
+}
+
+// whether a piece can get from the top of the board to loc and lock there, turning with the
+// system's kicks and only using 180s if the game has them. floating pieces aren't reachable,
+// they'd fall
+pub fn is_reachable(
+    board: &Board,
+    loc: PieceLocation,
+    system: RotationSystem,
+    allow_180: bool,
+) -> bool {
+    !board.collides(loc) && find_placement(board, loc, system, allow_180).is_some()
+}
+
+// None if there's no rotation between from and to (they're the same)
+const fn kicks(piece: Piece, from: Rotation, to: Rotation) -> Option<[(i8, i8); 6]> {
+    let kicks = match piece {
         Piece::O => [(0, 0); 6], // just be careful not to rotate the O piece at all lol
         Piece::I => match (from, to) {
             (Rotation::East, Rotation::North) => {
@@ -239,7 +347,7 @@ const fn kicks(piece: Piece, from: Rotation, to: Rotation) -> [(i8, i8); 6] {
             (Rotation::North, Rotation::South) => {
                 [(1, -1), (1, 0), (1, -1), (1, -1), (1, -1), (1, -1)]
             }
-            _ => panic!(), // this should never happen lol
+            _ => return None,
         },
         _ => match (from, to) {
             (Rotation::East, Rotation::North) => [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2), (0, 0)],
@@ -266,9 +374,76 @@ const fn kicks(piece: Piece, from: Rotation, to: Rotation) -> [(i8, i8); 6] {
             (Rotation::North, Rotation::South) => {
                 [(0, 0), (0, 1), (1, 1), (-1, 1), (1, 0), (-1, 0)]
             }
-            _ => panic!(), // this should never happen lol
+            _ => return None,
         },
-    }
+    };
+    Some(kicks)
+}
+
+// guideline srs in the same coordinates as kicks. jlstz kick the same as srs+, the i kicks differ
+// by mirroring and there's nothing for 180s. the last kick is repeated to fill out six
+const fn srs_kicks(piece: Piece, from: Rotation, to: Rotation) -> Option<[(i8, i8); 6]> {
+    let kicks = match (piece, from, to) {
+        (_, Rotation::North, Rotation::South)
+        | (_, Rotation::South, Rotation::North)
+        | (_, Rotation::East, Rotation::West)
+        | (_, Rotation::West, Rotation::East) => return None,
+        (Piece::I, Rotation::North, Rotation::East) => {
+            [(1, 0), (-1, 0), (2, 0), (-1, -1), (2, 2), (2, 2)]
+        }
+        (Piece::I, Rotation::East, Rotation::North) => {
+            [(-1, 0), (1, 0), (-2, 0), (1, 1), (-2, -2), (-2, -2)]
+        }
+        (Piece::I, Rotation::East, Rotation::South) => {
+            [(0, -1), (-1, -1), (2, -1), (-1, 1), (2, -2), (2, -2)]
+        }
+        (Piece::I, Rotation::South, Rotation::East) => {
+            [(0, 1), (1, 1), (-2, 1), (1, -1), (-2, 2), (-2, 2)]
+        }
+        (Piece::I, Rotation::South, Rotation::West) => {
+            [(-1, 0), (1, 0), (-2, 0), (1, 1), (-2, -2), (-2, -2)]
+        }
+        (Piece::I, Rotation::West, Rotation::South) => {
+            [(1, 0), (-1, 0), (2, 0), (-1, -1), (2, 2), (2, 2)]
+        }
+        (Piece::I, Rotation::West, Rotation::North) => {
+            [(0, 1), (1, 1), (-2, 1), (1, -1), (-2, 2), (-2, 2)]
+        }
+        (Piece::I, Rotation::North, Rotation::West) => {
+            [(0, -1), (-1, -1), (2, -1), (-1, 1), (2, -2), (2, -2)]
+        }
+        _ => return kicks(piece, from, to),
+    };
+    Some(kicks)
+}
+
+const fn jstris_kicks(piece: Piece, from: Rotation, to: Rotation) -> Option<[(i8, i8); 6]> {
+    let kicks = match (piece, from, to) {
+        (Piece::O, _, _) => [(0, 0); 6],
+        // the i's first kick is the one that turns it in place around its own center
+        (Piece::I, Rotation::North, Rotation::South) => {
+            [(1, -1), (1, 0), (1, 0), (1, 0), (1, 0), (1, 0)]
+        }
+        (Piece::I, Rotation::East, Rotation::West) => {
+            [(-1, -1), (0, -1), (0, -1), (0, -1), (0, -1), (0, -1)]
+        }
+        (Piece::I, Rotation::South, Rotation::North) => {
+            [(-1, 1), (-1, 0), (-1, 0), (-1, 0), (-1, 0), (-1, 0)]
+        }
+        (Piece::I, Rotation::West, Rotation::East) => {
+            [(1, 1), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1)]
+        }
+        (_, Rotation::North, Rotation::South) => [(0, 0), (0, 1), (0, 1), (0, 1), (0, 1), (0, 1)],
+        (_, Rotation::East, Rotation::West) => [(0, 0), (1, 0), (1, 0), (1, 0), (1, 0), (1, 0)],
+        (_, Rotation::South, Rotation::North) => {
+            [(0, 0), (0, -1), (0, -1), (0, -1), (0, -1), (0, -1)]
+        }
+        (_, Rotation::West, Rotation::East) => {
+            [(0, 0), (-1, 0), (-1, 0), (-1, 0), (-1, 0), (-1, 0)]
+        }
+        _ => return srs_kicks(piece, from, to),
+    };
+    Some(kicks)
 }
 
 #[derive(Debug, Clone)]
@@ -285,7 +460,7 @@ impl CollisionMap {
         for (dx, dy) in rotation.rotate_blocks(piece.blocks()) {
             for x in 0..10 {
                 let c = board
-                    .cols
+                    .cols()
                     .get((x + dx) as usize)
                     .map(|c| c.0)
                     .unwrap_or(FULL_HEIGHT);
@@ -297,7 +472,7 @@ impl CollisionMap {
             }
         }
 
-        let max_height = board.cols.into_iter().map(Column::height).max().unwrap();
+        let max_height = board.max_height();
         let mut all_valid: [u64; 10] = [(1 << (max_height + 3)) - 1; 10];
         let mut explored = [1 << (max_height + 2); 10];
         for x in 0..10 {
@@ -338,60 +513,19 @@ impl CollisionMap {
 
 impl fmt::Display for CollisionMap {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let outstr = (0..20)
-            .rev()
-            .map(|x| {
-                let explored = self
-                    .explored
-                    .iter()
-                    .map(move |v| {
-                        String::from(if (v & (1 << x)) == 0 {
-                            "⬜️"
-                        } else {
-                            "🟩"
-                        })
-                    })
-                    .collect::<Vec<String>>()
-                    .join("");
-                let all_valid = self
-                    .all_valid
-                    .iter()
-                    .map(move |v| {
-                        String::from(if (v & (1 << x)) == 0 {
-                            "⬜️"
-                        } else {
-                            "🟩"
-                        })
-                    })
-                    .collect::<Vec<String>>()
-                    .join("");
-                let obstructed = self
-                    .obstructed
-                    .iter()
-                    .map(move |v| {
-                        String::from(if (v & (1 << x)) == 0 {
-                            "⬜️"
-                        } else {
-                            "🟩"
-                        })
-                    })
-                    .collect::<Vec<String>>()
-                    .join("");
-                let spin_loc = self
-                    .spin_loc
-                    .iter()
-                    .map(move |v| {
-                        String::from(if (v & (1 << x)) == 0 {
-                            "⬜️"
-                        } else {
-                            "🟩"
-                        })
-                    })
-                    .collect::<Vec<String>>()
-                    .join("");
-                format!("{obstructed}     {all_valid}     {explored}     {spin_loc}")
-            })
-            .collect::<Vec<String>>();
-        write!(f, "Obstructed               All valid                Explored                 Spin location\n{}", outstr.join("\n"))
+        let boards = [
+            self.obstructed,
+            self.all_valid,
+            self.explored,
+            self.spin_loc,
+        ]
+        .map(|cols| Board::new(cols.map(Column)));
+        let titles = ["Obstructed", "All valid", "Explored", "Spin location"];
+        let views: Vec<View> = boards
+            .iter()
+            .zip(titles)
+            .map(|(board, title)| View::new(board).title(title).height(20))
+            .collect();
+        write!(f, "{}", side_by_side(&views, Style::Emoji, 5))
     }
 }
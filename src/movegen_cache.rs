@@ -0,0 +1,149 @@
+use crate::data::*;
+use crate::movegen::{movegen_piece_in, RotationSystem};
+
+use rustc_hash::FxHashMap;
+use serde::{Deserialize, Serialize};
+
+const NONE: usize = usize::MAX;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+impl CacheStats {
+    // 0 before anything was looked up
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 0.0,
+            total => self.hits as f64 / total as f64,
+        }
+    }
+}
+
+struct Entry {
+    key: (u64, Piece),
+    // a hash can collide, so hits get checked against the board itself
+    cols: [Column; 10],
+    locs: Vec<PieceLocation>,
+    // neighbours in the recency list, NONE at the ends
+    newer: usize,
+    older: usize,
+}
+
+// movegen results for the last `capacity` board and piece pairs looked up, for searches that run
+// into the same board down different lines. the kicks and 180s are fixed for the whole cache
+pub struct MovegenCache {
+    capacity: usize,
+    system: RotationSystem,
+    allow_180: bool,
+    map: FxHashMap<(u64, Piece), usize>,
+    entries: Vec<Entry>,
+    // most and least recently used entries
+    newest: usize,
+    oldest: usize,
+    pub stats: CacheStats,
+}
+
+impl MovegenCache {
+    pub fn new(capacity: usize, system: RotationSystem, allow_180: bool) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            system,
+            allow_180,
+            map: FxHashMap::default(),
+            entries: vec![],
+            newest: NONE,
+            oldest: NONE,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // same as movegen_piece_in with the cache's kicks and 180s
+    pub fn get(&mut self, board: &Board, piece: Piece) -> &[PieceLocation] {
+        let key = (board.hash(), piece);
+        let i = match self.map.get(&key) {
+            Some(&i) if self.entries[i].cols == *board.cols() => {
+                self.stats.hits += 1;
+                self.unlink(i);
+                i
+            }
+            found => {
+                self.stats.misses += 1;
+                let locs = movegen_piece_in(board, piece, self.system, self.allow_180);
+                let entry = Entry {
+                    key,
+                    cols: *board.cols(),
+                    locs,
+                    newer: NONE,
+                    older: NONE,
+                };
+                // a collision takes over the other board's slot, otherwise the oldest one goes
+                // once the cache is full
+                let i = match found {
+                    Some(&i) => {
+                        self.unlink(i);
+                        i
+                    }
+                    None if self.entries.len() < self.capacity => {
+                        self.entries.push(entry);
+                        self.map.insert(key, self.entries.len() - 1);
+                        self.push_newest(self.entries.len() - 1);
+                        return &self.entries[self.newest].locs;
+                    }
+                    None => {
+                        let i = self.oldest;
+                        self.unlink(i);
+                        self.map.remove(&self.entries[i].key);
+                        self.map.insert(key, i);
+                        i
+                    }
+                };
+                self.entries[i] = entry;
+                i
+            }
+        };
+        self.push_newest(i);
+        &self.entries[i].locs
+    }
+
+    // every placement for next and the hold piece, like beam_search::candidates
+    pub fn candidates(&mut self, game: &Game, next: Piece, allow_hold: bool) -> Vec<PieceLocation> {
+        let mut locs = self.get(&game.board, next).to_vec();
+        if allow_hold {
+            locs.extend_from_slice(self.get(&game.board, game.hold()));
+        }
+        locs
+    }
+
+    fn unlink(&mut self, i: usize) {
+        let Entry { newer, older, .. } = self.entries[i];
+        match newer {
+            NONE => self.newest = older,
+            n => self.entries[n].older = older,
+        }
+        match older {
+            NONE => self.oldest = newer,
+            o => self.entries[o].newer = newer,
+        }
+    }
+
+    fn push_newest(&mut self, i: usize) {
+        self.entries[i].newer = NONE;
+        self.entries[i].older = self.newest;
+        match self.newest {
+            NONE => self.oldest = i,
+            n => self.entries[n].newer = i,
+        }
+        self.newest = i;
+    }
+}
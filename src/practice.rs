@@ -0,0 +1,160 @@
+use crate::analyze::{compare, Comparison};
+use crate::beam_search::*;
+use crate::data::*;
+use crate::eval::Eval;
+use crate::movegen::{find_placement, rotate};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    Left,
+    Right,
+    // one row down
+    SoftDrop,
+    HardDrop,
+    RotateCw,
+    RotateCcw,
+    Rotate180,
+    Hold,
+}
+
+// a game played by hand, one input at a time, with the bot around to ask for help
+pub struct Practice {
+    pub game: Game,
+    // the current piece is queue[0]
+    pub queue: Vec<Piece>,
+    // the piece being moved. it's the hold piece after a hold
+    pub active: PieceLocation,
+    pub eval: Eval,
+    pub search: SearchConfig,
+    pub pieces: usize,
+    pub over: bool,
+    held: bool,
+    // spins only count if the last thing the piece did was turn
+    rotated: bool,
+    rng: StdRng,
+}
+
+impl Practice {
+    pub fn new(seed: u64, eval: Eval, search: SearchConfig) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (hold, queue) = gen_queue(4, &mut rng);
+        let active = queue[0].spawn();
+        Self {
+            game: Game::new(Some(hold)),
+            queue,
+            active,
+            eval,
+            search,
+            pieces: 0,
+            over: false,
+            held: false,
+            rotated: false,
+            rng,
+        }
+    }
+
+    // the bot's move for the position as it is now, before the current piece is placed
+    pub fn hint(&self) -> Result<PieceLocation, SearchError> {
+        search(&self.game, &self.queue, &self.eval, &self.search)
+    }
+
+    // returns how the placement compared to the bot's once the piece locks
+    pub fn input(&mut self, input: Input) -> Option<Comparison> {
+        if self.over {
+            return None;
+        }
+        let turns = matches!(input, Input::RotateCw | Input::RotateCcw | Input::Rotate180);
+        // the o's kicks are all (0, 0), turning it through them would move its cells around
+        if turns && self.active.piece == Piece::O {
+            return None;
+        }
+        let board = self.game.board;
+        let system = self.search.rotation_system;
+        let moved = |dx, dy| PieceLocation {
+            x: self.active.x + dx,
+            y: self.active.y + dy,
+            ..self.active
+        };
+        let next = match input {
+            Input::Left => Some(moved(-1, 0)).filter(|l| !board.collides(*l)),
+            Input::Right => Some(moved(1, 0)).filter(|l| !board.collides(*l)),
+            Input::SoftDrop => Some(moved(0, -1)).filter(|l| !board.collides(*l)),
+            Input::RotateCw => rotate(
+                &board,
+                self.active,
+                self.active.rotation.rotate_right(),
+                system,
+            ),
+            Input::RotateCcw => rotate(
+                &board,
+                self.active,
+                self.active.rotation.rotate_left(),
+                system,
+            ),
+            Input::Rotate180 if self.search.allow_180 => rotate(
+                &board,
+                self.active,
+                self.active.rotation.rotate_180(),
+                system,
+            ),
+            Input::Rotate180 => None,
+            Input::Hold => {
+                if !self.held && self.search.allow_hold {
+                    self.held = true;
+                    self.rotated = false;
+                    let piece = match self.active.piece == self.queue[0] {
+                        true => self.game.hold(),
+                        false => self.queue[0],
+                    };
+                    self.active = piece.spawn();
+                }
+                return None;
+            }
+            Input::HardDrop => return Some(self.lock()),
+        };
+        if let Some(loc) = next {
+            self.active = loc;
+            self.rotated = turns;
+        }
+        None
+    }
+
+    fn lock(&mut self) -> Comparison {
+        let dropped = self.game.board.hard_drop(self.active);
+        let rotated = self.rotated && dropped == self.active;
+        let human = match find_placement(
+            &self.game.board,
+            dropped,
+            self.search.rotation_system,
+            self.search.allow_180,
+        ) {
+            Some(found) => PieceLocation {
+                spun: found.spun && rotated,
+                ..found
+            },
+            // movegen doesn't know every way to get somewhere, so the flags are a guess here
+            None => PieceLocation {
+                spun: false,
+                possible_line_clear: true,
+                ..dropped
+            },
+        };
+
+        let comparison = compare(&self.game, &self.queue, human, &self.eval, &self.search);
+
+        self.game.advance(self.queue[0], human);
+        self.queue.remove(0);
+        if self.queue.len() < self.search.preview + 1 {
+            self.queue.extend(gen_bags(1, &mut self.rng));
+        }
+        self.pieces += 1;
+        self.held = false;
+        self.rotated = false;
+        self.active = self.queue[0].spawn();
+        self.over = self.game.board.collides(self.active);
+        comparison
+    }
+}
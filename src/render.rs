@@ -0,0 +1,267 @@
+use crate::data::*;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    // one character per cell, pieces are drawn as their letter
+    Ascii,
+    // terminal background colors, two columns per cell
+    Ansi,
+    Emoji,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Cell {
+    Empty,
+    // with the piece that filled it, if the view has colors
+    Filled(Option<Piece>),
+    // the highlighted piece, and whether it spun
+    Piece(Piece, bool),
+    Ghost(Piece),
+    // where the bot would put its piece
+    Hint(Piece),
+}
+
+impl Style {
+    // how many terminal columns one cell takes up
+    fn cell_width(self) -> usize {
+        match self {
+            Style::Ascii => 1,
+            Style::Ansi | Style::Emoji => 2,
+        }
+    }
+
+    fn cell(self, cell: Cell) -> String {
+        match self {
+            Style::Ascii => match cell {
+                Cell::Empty => ".".into(),
+                Cell::Filled(_) => "#".into(),
+                Cell::Piece(piece, _) => format!("{piece:?}"),
+                Cell::Ghost(_) => "+".into(),
+                Cell::Hint(_) => "*".into(),
+            },
+            Style::Ansi => match cell {
+                Cell::Empty => "\x1b[90m .\x1b[0m".into(),
+                Cell::Filled(None) => "\x1b[48;5;244m  \x1b[0m".into(),
+                Cell::Filled(Some(piece)) => format!("\x1b[48;5;{}m  \x1b[0m", ansi_color(piece)),
+                Cell::Piece(piece, _) => format!("\x1b[48;5;{}m  \x1b[0m", ansi_color(piece)),
+                Cell::Ghost(piece) => format!("\x1b[38;5;{}m[]\x1b[0m", ansi_color(piece)),
+                Cell::Hint(piece) => format!("\x1b[1;38;5;{}m<>\x1b[0m", ansi_color(piece)),
+            },
+            Style::Emoji => match cell {
+                Cell::Empty => "⬜️".into(),
+                Cell::Filled(_) => "🟩".into(),
+                Cell::Piece(_, true) => "🟨".into(),
+                Cell::Piece(_, false) => "🟥".into(),
+                Cell::Ghost(_) => "🔲".into(),
+                Cell::Hint(_) => "🔳".into(),
+            },
+        }
+    }
+}
+
+// 256 color palette entries closest to the guideline colors
+fn ansi_color(piece: Piece) -> u8 {
+    match piece {
+        Piece::I => 51,
+        Piece::O => 226,
+        Piece::T => 129,
+        Piece::L => 208,
+        Piece::J => 21,
+        Piece::S => 46,
+        Piece::Z => 196,
+    }
+}
+
+// a board plus whatever should be drawn with it. rows come out top down, with the hold, queue and
+// notes listed to the right of the board
+#[derive(Debug, Clone)]
+pub struct View<'a> {
+    pub(crate) board: &'a Board,
+    colors: Option<&'a Colors>,
+    pub(crate) piece: Option<PieceLocation>,
+    ghost: bool,
+    hint: Option<PieceLocation>,
+    pub(crate) hold: Option<Piece>,
+    pub(crate) queue: &'a [Piece],
+    pub(crate) notes: Vec<String>,
+    pub(crate) title: Option<String>,
+    height: Option<u8>,
+}
+
+impl<'a> View<'a> {
+    pub fn new(board: &'a Board) -> Self {
+        Self {
+            board,
+            colors: None,
+            piece: None,
+            ghost: false,
+            hint: None,
+            hold: None,
+            queue: &[],
+            notes: vec![],
+            title: None,
+            height: None,
+        }
+    }
+
+    pub fn colored(board: &'a ColoredBoard) -> Self {
+        Self::new(&board.board).colors(&board.colors)
+    }
+
+    // which piece filled each cell, for the backends that draw pieces in their own color
+    pub fn colors(mut self, colors: &'a Colors) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    // draw a piece on top of the board, it doesn't have to be resting on anything
+    pub fn piece(mut self, loc: PieceLocation) -> Self {
+        self.piece = Some(loc);
+        self
+    }
+
+    // also draw where the piece would land
+    pub fn ghost(mut self, ghost: bool) -> Self {
+        self.ghost = ghost;
+        self
+    }
+
+    // a suggested placement, drawn under the piece and over its ghost
+    pub fn hint(mut self, hint: PieceLocation) -> Self {
+        self.hint = Some(hint);
+        self
+    }
+
+    pub fn hold(mut self, hold: Piece) -> Self {
+        self.hold = Some(hold);
+        self
+    }
+
+    pub fn queue(mut self, queue: &'a [Piece]) -> Self {
+        self.queue = queue;
+        self
+    }
+
+    // a line of text for the side panel, under the hold and queue
+    pub fn note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    // rows to draw, by default just enough to fit the stack and the piece with one empty row
+    pub fn height(mut self, height: u8) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    pub(crate) fn cell(&self, x: i8, y: i8) -> Cell {
+        if let Some(loc) = self.piece {
+            if loc.blocks().contains(&(x, y)) {
+                return Cell::Piece(loc.piece, loc.spun);
+            }
+        }
+        if let Some(hint) = self.hint {
+            if hint.blocks().contains(&(x, y)) {
+                return Cell::Hint(hint.piece);
+            }
+        }
+        if let Some(loc) = self.piece {
+            if self.ghost && self.board.hard_drop(loc).blocks().contains(&(x, y)) {
+                return Cell::Ghost(loc.piece);
+            }
+        }
+        if self.board.cols()[x as usize].0 & 1 << y != 0 {
+            Cell::Filled(self.colors.and_then(|c| c.get(x as usize, y as usize)))
+        } else {
+            Cell::Empty
+        }
+    }
+
+    pub(crate) fn rows(&self) -> u8 {
+        let height = self.height.unwrap_or_else(|| {
+            let top = self.piece.map_or(0, |loc| {
+                loc.blocks().iter().map(|&(_, y)| y + 1).max().unwrap()
+            });
+            self.board.max_height().max(top.max(0) as u8) + 1
+        });
+        height.min(64)
+    }
+
+    // every line along with how many terminal columns it takes up, escape codes don't count
+    fn lines(&self, style: Style) -> Vec<(String, usize)> {
+        let board_width = 10 * style.cell_width();
+
+        let mut side = vec![];
+        if let Some(hold) = self.hold {
+            side.push(format!("hold  {hold:?}"));
+        }
+        if !self.queue.is_empty() {
+            let queue: String = self.queue.iter().map(|p| format!("{p:?}")).collect();
+            side.push(format!("next  {queue}"));
+        }
+        side.extend(self.notes.iter().cloned());
+
+        let mut lines = vec![];
+        if let Some(title) = &self.title {
+            lines.push((title.clone(), title.chars().count()));
+        }
+        let rows = self.rows() as usize;
+        for (i, y) in (0..rows as i8).rev().enumerate() {
+            let mut line: String = (0..10).map(|x| style.cell(self.cell(x, y))).collect();
+            let mut width = board_width;
+            if let Some(text) = side.get(i).filter(|t| !t.is_empty()) {
+                line += &format!("   {text}");
+                width += 3 + text.chars().count();
+            }
+            lines.push((line, width));
+        }
+        // a short board still gets its whole side panel
+        for text in side.iter().skip(rows) {
+            let pad = " ".repeat(board_width + 3);
+            lines.push((
+                format!("{pad}{text}"),
+                board_width + 3 + text.chars().count(),
+            ));
+        }
+        lines
+    }
+
+    pub fn render(&self, style: Style) -> String {
+        let lines: Vec<String> = self.lines(style).into_iter().map(|(l, _)| l).collect();
+        lines.join("\n")
+    }
+}
+
+// several views next to each other, lined up at the bottom so the floors match
+pub fn side_by_side(views: &[View], style: Style, gap: usize) -> String {
+    let rendered: Vec<Vec<(String, usize)>> = views.iter().map(|v| v.lines(style)).collect();
+    let rows = rendered.iter().map(Vec::len).max().unwrap_or(0);
+    let widths: Vec<usize> = rendered
+        .iter()
+        .map(|lines| lines.iter().map(|&(_, w)| w).max().unwrap_or(0))
+        .collect();
+
+    let mut out = vec![];
+    for row in 0..rows {
+        let mut line = String::new();
+        for (i, lines) in rendered.iter().enumerate() {
+            let (text, width) = match (row + lines.len()).checked_sub(rows) {
+                Some(j) => (lines[j].0.as_str(), lines[j].1),
+                None => ("", 0),
+            };
+            line += text;
+            if i + 1 < rendered.len() {
+                line += &" ".repeat(widths[i] - width + gap);
+            }
+        }
+        out.push(line.trim_end().to_string());
+    }
+    out.join("\n")
+}
@@ -0,0 +1,152 @@
+use crate::beam_search::{SearchConfig, SearchStats};
+use crate::data::*;
+use crate::eval::Eval;
+
+use serde::{Deserialize, Serialize};
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Move {
+    // the piece that was up next. loc.piece is different from it when hold got used
+    pub piece: Piece,
+    pub loc: PieceLocation,
+    pub info: PlacementInfo,
+    pub stats: SearchStats,
+}
+
+// everything needed to watch a game again, or to play it again with the same pieces
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Replay {
+    // what the queue was generated from
+    pub seed: u64,
+    pub eval: Eval,
+    pub search: SearchConfig,
+    // the hold piece the game started with
+    pub hold: Piece,
+    // every piece the game saw, including the preview left over at the end
+    pub queue: Vec<Piece>,
+    pub moves: Vec<Move>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, eval: Eval, search: SearchConfig, hold: Piece) -> Self {
+        Self {
+            seed,
+            eval,
+            search,
+            hold,
+            queue: vec![],
+            moves: vec![],
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, serde_json::to_string(self)?)
+    }
+}
+
+// steps through a replay. positions go from 0 (nothing placed yet) to moves.len()
+pub struct Player<'a> {
+    pub replay: &'a Replay,
+    // the game before every move, and after the last one
+    games: Vec<Game>,
+    colors: Vec<Colors>,
+    position: usize,
+}
+
+impl<'a> Player<'a> {
+    // plays every move up front, so a replay file that got corrupted or edited into a move that
+    // can't be placed is an error here instead of a panic later
+    pub fn new(replay: &'a Replay) -> Result<Self, ParseError> {
+        let mut game = Game::new(Some(replay.hold));
+        let mut colors = Colors::default();
+        let mut games = vec![game];
+        let mut all_colors = vec![colors.clone()];
+        for (i, m) in replay.moves.iter().enumerate() {
+            let bad = |e: String| ParseError(format!("move {} of the replay {e}", i + 1));
+            if m.loc.piece != m.piece && m.loc.piece != game.hold() {
+                return Err(bad(format!(
+                    "places {:?}, which isn't the current or hold piece",
+                    m.loc.piece
+                )));
+            }
+            let mut board = game.board;
+            board
+                .try_place(m.loc)
+                .map_err(|e| bad(format!("can't be placed, {e}")))?;
+            let info = game.advance(m.piece, m.loc);
+            colors.place(m.loc, info.line_mask);
+            games.push(game);
+            all_colors.push(colors.clone());
+        }
+        Ok(Self {
+            replay,
+            games,
+            colors: all_colors,
+            position: 0,
+        })
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.replay.moves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replay.moves.is_empty()
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.games[self.position]
+    }
+
+    // which piece filled each cell of game().board
+    pub fn colors(&self) -> &Colors {
+        &self.colors[self.position]
+    }
+
+    // the move about to be played from here, None at the end
+    pub fn next_move(&self) -> Option<&Move> {
+        self.replay.moves.get(self.position)
+    }
+
+    // the move that got the game here, None at the start
+    pub fn last_move(&self) -> Option<&Move> {
+        self.position.checked_sub(1).map(|i| &self.replay.moves[i])
+    }
+
+    // the pieces still to come from here, starting with the current one
+    pub fn queue(&self) -> &[Piece] {
+        &self.replay.queue[self.position.min(self.replay.queue.len())..]
+    }
+
+    // these return false when there's nowhere to go
+    pub fn forward(&mut self) -> bool {
+        self.seek(self.position + 1)
+    }
+
+    pub fn back(&mut self) -> bool {
+        self.position > 0 && self.seek(self.position - 1)
+    }
+
+    pub fn seek(&mut self, position: usize) -> bool {
+        if position > self.len() {
+            return false;
+        }
+        self.position = position;
+        true
+    }
+}
@@ -0,0 +1,290 @@
+use crate::beam_search::*;
+use crate::data::*;
+use crate::eval::Eval;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+// every `interval` pieces, `lines` rows of garbage with a single random hole column come in
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default)]
+pub struct GarbageConfig {
+    pub interval: usize,
+    pub lines: u32,
+}
+
+impl Default for GarbageConfig {
+    fn default() -> Self {
+        Self {
+            interval: 7,
+            lines: 2,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SimConfig {
+    pub games: usize,
+    // pieces per game
+    pub pieces: usize,
+    // game i plays the queue (and garbage) seeded with seed + i, so every weight set sees the
+    // exact same games
+    pub seed: u64,
+    // the game is over once the stack gets above this height
+    pub top_out_height: u8,
+    pub garbage: Option<GarbageConfig>,
+    // if the search isn't seeded, each game seeds its eval noise with its own seed too
+    pub search: SearchConfig,
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        Self {
+            games: 4,
+            pieces: 500,
+            seed: 0,
+            top_out_height: 15,
+            garbage: None,
+            search: SearchConfig {
+                depth: 15,
+                width: 3000,
+                preview: 14,
+                ..SearchConfig::default()
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TopOut {
+    // the next piece couldn't spawn
+    Spawn,
+    // there was nowhere to put the next piece
+    NoMoves,
+    // the stack went above top_out_height
+    Height,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GameStats {
+    pub pieces: usize,
+    pub attack: u32,
+    pub lines: u32,
+    pub b2b_clears: u32,
+    pub max_b2b: u64,
+    // length of every b2b chain that got broken, plus the one still going at the end
+    pub b2b_chains: Vec<u64>,
+    // spin clears by piece, indexed by Piece as usize
+    pub spins: [u32; 7],
+    pub garbage_received: u32,
+    pub garbage_cleared: u32,
+    // None if the game lasted all of its pieces
+    pub top_out: Option<TopOut>,
+}
+
+// plays one seeded game from an empty board
+pub fn play(eval: &Eval, config: &SimConfig, seed: u64) -> GameStats {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let bags = (config.pieces + config.search.preview) as u32 / 7 + 2;
+    let (hold, mut queue) = gen_queue(bags, &mut rng);
+    let search_config = SearchConfig {
+        seed: config.search.seed.or(Some(seed)),
+        ..config.search
+    };
+
+    let mut game = Game::new(Some(hold));
+    let mut stats = GameStats::default();
+    // garbage always sits in one block at the bottom of the board
+    let mut garbage_rows: u32 = 0;
+
+    for p in 0..config.pieces {
+        if let Some(garbage) = config.garbage {
+            if p > 0 && p % garbage.interval.max(1) == 0 {
+                game.board
+                    .add_garbage(garbage.lines, rng.random_range(0..10));
+                garbage_rows = (garbage_rows + garbage.lines).min(63);
+                stats.garbage_received += garbage.lines;
+            }
+        }
+
+        let loc = match search(&game, &queue, eval, &search_config) {
+            Ok(loc) => loc,
+            Err(e) => {
+                stats.top_out = Some(match e {
+                    SearchError::ToppedOut => TopOut::Spawn,
+                    SearchError::NoMoves | SearchError::EmptyQueue => TopOut::NoMoves,
+                });
+                break;
+            }
+        };
+        let b2b = game.b2b();
+        let info = game.advance(queue[0], loc);
+        queue.remove(0);
+        stats.pieces += 1;
+
+        stats.attack += info.attack;
+        stats.lines += info.lines_cleared;
+        if info.spin && info.lines_cleared > 0 {
+            stats.spins[loc.piece as usize] += 1;
+        }
+        if game.b2b() > b2b {
+            stats.b2b_clears += 1;
+        } else if b2b > 0 && game.b2b() == 0 {
+            stats.b2b_chains.push(b2b);
+        }
+        stats.max_b2b = stats.max_b2b.max(game.b2b());
+        let cleared = (info.line_mask & ((1 << garbage_rows) - 1)).count_ones();
+        stats.garbage_cleared += cleared;
+        garbage_rows -= cleared;
+
+        if game.board.max_height() > config.top_out_height {
+            stats.top_out = Some(TopOut::Height);
+            break;
+        }
+    }
+    if game.b2b() > 0 {
+        stats.b2b_chains.push(game.b2b());
+    }
+    stats
+}
+
+// runs f(0..jobs) over all cores, results come back in job order
+pub(crate) fn parallel_map<T: Send>(jobs: usize, f: impl Fn(usize) -> T + Sync) -> Vec<T> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(jobs));
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    std::thread::scope(|s| {
+        for _ in 0..threads.min(jobs) {
+            s.spawn(|| loop {
+                let job = next.fetch_add(1, Ordering::Relaxed);
+                if job >= jobs {
+                    break;
+                }
+                let result = f(job);
+                results.lock().unwrap().push((job, result));
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|&(job, _)| job);
+    results.into_iter().map(|(_, r)| r).collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub games: usize,
+    pub pieces: usize,
+    pub pieces_min: usize,
+    pub pieces_median: usize,
+    pub pieces_max: usize,
+    pub attack_per_piece: f64,
+    pub lines_per_piece: f64,
+    pub b2b_chains: usize,
+    pub b2b_chain_mean: f64,
+    pub b2b_chain_max: u64,
+    // spin clears by piece name
+    pub spins: BTreeMap<String, u32>,
+    // how fast a single game runs, in pieces per second
+    pub pps: f64,
+    // how many games ended which way. "survived" means it lasted all of its pieces
+    pub endings: BTreeMap<String, usize>,
+    pub per_game: Vec<GameStats>,
+}
+
+// plays config.games seeded games in parallel and sums them up
+pub fn simulate(eval: &Eval, config: &SimConfig) -> Summary {
+    let timed = parallel_map(config.games, |i| {
+        let start = Instant::now();
+        let stats = play(eval, config, config.seed.wrapping_add(i as u64));
+        (stats, start.elapsed().as_secs_f64())
+    });
+    let seconds: f64 = timed.iter().map(|(_, s)| s).sum();
+    let per_game: Vec<GameStats> = timed.into_iter().map(|(g, _)| g).collect();
+
+    let mut lengths: Vec<usize> = per_game.iter().map(|g| g.pieces).collect();
+    lengths.sort();
+    let pieces: usize = lengths.iter().sum();
+    let chains: Vec<u64> = per_game
+        .iter()
+        .flat_map(|g| g.b2b_chains.iter().copied())
+        .collect();
+
+    let names = [
+        Piece::I,
+        Piece::O,
+        Piece::T,
+        Piece::L,
+        Piece::J,
+        Piece::S,
+        Piece::Z,
+    ];
+    let spins = names
+        .iter()
+        .map(|&p| {
+            let n = per_game.iter().map(|g| g.spins[p as usize]).sum();
+            (format!("{p:?}"), n)
+        })
+        .collect();
+
+    let mut endings = BTreeMap::new();
+    for g in &per_game {
+        let ending = match g.top_out {
+            Some(cause) => format!("{cause:?}"),
+            None => "Survived".to_string(),
+        };
+        *endings.entry(ending).or_insert(0) += 1;
+    }
+
+    let per_piece = |x: u32| x as f64 / pieces.max(1) as f64;
+    Summary {
+        games: per_game.len(),
+        pieces,
+        pieces_min: lengths.first().copied().unwrap_or(0),
+        pieces_median: lengths.get(lengths.len() / 2).copied().unwrap_or(0),
+        pieces_max: lengths.last().copied().unwrap_or(0),
+        attack_per_piece: per_piece(per_game.iter().map(|g| g.attack).sum()),
+        lines_per_piece: per_piece(per_game.iter().map(|g| g.lines).sum()),
+        b2b_chains: chains.len(),
+        b2b_chain_mean: chains.iter().sum::<u64>() as f64 / chains.len().max(1) as f64,
+        b2b_chain_max: chains.iter().copied().max().unwrap_or(0),
+        spins,
+        pps: pieces as f64 / seconds.max(f64::EPSILON),
+        endings,
+        per_game,
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "games                {}", self.games)?;
+        writeln!(
+            f,
+            "pieces survived      {} total   min {}   median {}   max {}",
+            self.pieces, self.pieces_min, self.pieces_median, self.pieces_max
+        )?;
+        writeln!(f, "attack per piece     {:.3}", self.attack_per_piece)?;
+        writeln!(f, "lines per piece      {:.3}", self.lines_per_piece)?;
+        writeln!(
+            f,
+            "b2b chains           {}   mean {:.2}   max {}",
+            self.b2b_chains, self.b2b_chain_mean, self.b2b_chain_max
+        )?;
+        let spins: Vec<String> = self.spins.iter().map(|(p, n)| format!("{p} {n}")).collect();
+        writeln!(f, "spin clears          {}", spins.join("   "))?;
+        writeln!(f, "pps                  {:.2}", self.pps)?;
+        let endings: Vec<String> = self
+            .endings
+            .iter()
+            .map(|(e, n)| format!("{e} {n}"))
+            .collect();
+        write!(f, "endings              {}", endings.join("   "))
+    }
+}
@@ -0,0 +1,121 @@
+use crate::eval::Eval;
+use crate::fitness::{fitness, FitnessConfig};
+use crate::genetic::normalized;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use std::io;
+use std::path::Path;
+
+// simultaneous perturbation stochastic approximation. every iteration nudges all 14 weights at
+// once in a random ±c direction, plays both sides on the same queue, and steps along the
+// estimated gradient. needs 2 fitness evaluations per iteration no matter how many weights there
+// are, and unlike the ga it follows correlated weights together
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct SpsaConfig {
+    pub iterations: usize,
+    // step size is a / (iteration + 1 + big_a)^alpha
+    pub a: f32,
+    pub big_a: f32,
+    pub alpha: f32,
+    // perturbation size is c / (iteration + 1)^gamma
+    pub c: f32,
+    pub gamma: f32,
+    // how each side gets scored. both play the same seeded games
+    pub fitness: FitnessConfig,
+}
+
+impl Default for SpsaConfig {
+    fn default() -> Self {
+        Self {
+            iterations: 200,
+            a: 2000.0,
+            big_a: 20.0,
+            alpha: 0.602,
+            c: 50.0,
+            gamma: 0.101,
+            fitness: FitnessConfig::default(),
+        }
+    }
+}
+
+// what one iteration did, after the step
+#[derive(Debug, Clone, Copy)]
+pub struct Iteration {
+    pub k: usize,
+    pub weights: [f32; 14],
+    pub f_plus: f32,
+    pub f_minus: f32,
+    pub step: f32,
+    pub perturbation: f32,
+}
+
+// runs spsa starting from `start` (or random weights), writing the current weights to `out` after
+// every iteration. returns the final weights
+pub fn run_spsa(
+    config: &SpsaConfig,
+    start: Option<[f32; 14]>,
+    seed: u64,
+    out: &Path,
+) -> io::Result<[f32; 14]> {
+    let fitness = |weights: &[f32; 14]| fitness(&Eval::from(*weights), &config.fitness);
+    spsa(config, start, seed, fitness, |it| {
+        println!(
+            "\x1b[1mITERATION {}/{}\x1b[0m   f+ {:.3}   f- {:.3}   step {:.2}   perturbation {:.2}",
+            it.k + 1,
+            config.iterations,
+            it.f_plus,
+            it.f_minus,
+            it.step,
+            it.perturbation
+        );
+        Eval::from(it.weights).save(out)
+    })
+}
+
+// the spsa loop on its own, scoring weights with `fitness` (config.fitness is ignored) and
+// handing every iteration to `each`, which can stop the run with an error
+pub fn spsa(
+    config: &SpsaConfig,
+    start: Option<[f32; 14]>,
+    seed: u64,
+    fitness: impl Fn(&[f32; 14]) -> f32 + Sync,
+    mut each: impl FnMut(&Iteration) -> io::Result<()>,
+) -> io::Result<[f32; 14]> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut theta =
+        start.unwrap_or_else(|| normalized([0; 14].map(|_| rng.random_range(-1.0..=1.0))));
+
+    for k in 0..config.iterations {
+        let a = config.a / (k as f32 + 1.0 + config.big_a).powf(config.alpha);
+        let c = config.c / (k as f32 + 1.0).powf(config.gamma);
+        let delta: [f32; 14] = [0; 14].map(|_| if rng.random() { 1.0 } else { -1.0 });
+
+        let plus: [f32; 14] = std::array::from_fn(|i| theta[i] + c * delta[i]);
+        let minus: [f32; 14] = std::array::from_fn(|i| theta[i] - c * delta[i]);
+
+        // both sides play the same games so the difference is down to the weights
+        let (f_plus, f_minus) = rayon::join(|| fitness(&plus), || fitness(&minus));
+
+        for i in 0..14 {
+            // fitness is maximized, so step up the gradient
+            theta[i] += a * (f_plus - f_minus) / (2.0 * c * delta[i]);
+        }
+        // keep the weights on the same scale as the ga so a and c mean the same thing throughout
+        theta = normalized(theta);
+
+        each(&Iteration {
+            k,
+            weights: theta,
+            f_plus,
+            f_minus,
+            step: a,
+            perturbation: c,
+        })?;
+    }
+
+    Ok(theta)
+}
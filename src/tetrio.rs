@@ -0,0 +1,394 @@
+use crate::data::*;
+use crate::import::{IllegalMove, ImportedGame, Playfield};
+use crate::movegen::RotationSystem;
+
+use serde_json::Value;
+
+// tetr.io's randomizer, a park-miller generator seeded with the game's seed
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        match seed % 2147483647 {
+            0 => Self(2147483646),
+            t => Self(t),
+        }
+    }
+
+    fn next_float(&mut self) -> f64 {
+        self.0 = 16807 * self.0 % 2147483647;
+        (self.0 - 1) as f64 / 2147483646.0
+    }
+
+    // the next 7-bag, shuffled from the same starting order tetr.io uses
+    fn bag(&mut self) -> Vec<Piece> {
+        let mut bag = vec![
+            Piece::Z,
+            Piece::L,
+            Piece::O,
+            Piece::S,
+            Piece::I,
+            Piece::J,
+            Piece::T,
+        ];
+        for i in (1..bag.len()).rev() {
+            let r = (self.next_float() * (i + 1) as f64) as usize;
+            bag.swap(i, r);
+        }
+        bag
+    }
+}
+
+// the game options and handling a replay was played with, in frames
+struct Options {
+    seed: u64,
+    allow_180: bool,
+    g: f64,
+    gincrease: f64,
+    gmargin: f64,
+    locktime: f64,
+    lockresets: u32,
+    garbagespeed: u64,
+    garbagecap: u32,
+    das: f64,
+    arr: f64,
+    sdf: f64,
+}
+
+impl Options {
+    fn parse(options: &Value) -> Result<Self, ParseError> {
+        let num = |v: &Value, default: f64| v.as_f64().unwrap_or(default);
+        match options["bagtype"].as_str() {
+            None | Some("7-bag") => {}
+            Some(other) => {
+                return Err(ParseError(format!(
+                    "only 7-bag games can be imported, not {other}"
+                )))
+            }
+        }
+        let handling = &options["handling"];
+        Ok(Self {
+            seed: options["seed"]
+                .as_f64()
+                .ok_or_else(|| ParseError("replay has no seed".to_string()))?
+                as u64,
+            allow_180: options["allow180"].as_bool().unwrap_or(true),
+            g: num(&options["g"], 0.02),
+            gincrease: num(&options["gincrease"], 0.0),
+            gmargin: num(&options["gmargin"], 0.0),
+            locktime: num(&options["locktime"], 30.0),
+            lockresets: num(&options["lockresets"], 15.0) as u32,
+            garbagespeed: num(&options["garbagespeed"], 20.0) as u64,
+            garbagecap: num(&options["garbagecap"], 8.0) as u32,
+            das: num(&handling["das"], 10.0),
+            arr: num(&handling["arr"], 2.0),
+            sdf: num(&handling["sdf"], 6.0),
+        })
+    }
+}
+
+// garbage on its way in, rising once it has waited until `frame`
+struct Incoming {
+    frame: u64,
+    lines: u32,
+    hole: usize,
+}
+
+// plays the recorded inputs back frame by frame: das and arr for held directions, soft drop and
+// gravity, lock delay, and garbage that rises after a placement that clears nothing
+struct Sim {
+    field: Playfield,
+    rng: Rng,
+    options: Options,
+    frame: u64,
+    left: bool,
+    right: bool,
+    // the direction das is charging for, -1, 1 or 0
+    dir: i8,
+    das: f64,
+    arr: f64,
+    soft: bool,
+    g: f64,
+    fall: f64,
+    lock: f64,
+    resets: u32,
+    incoming: Vec<Incoming>,
+}
+
+impl Sim {
+    fn new(options: Options) -> Self {
+        let mut rng = Rng::new(options.seed);
+        let mut pieces = rng.bag();
+        pieces.extend(rng.bag());
+        Self {
+            field: Playfield::new(pieces, RotationSystem::SrsPlus, options.allow_180),
+            rng,
+            g: options.g,
+            options,
+            frame: 0,
+            left: false,
+            right: false,
+            dir: 0,
+            das: 0.0,
+            arr: 0.0,
+            soft: false,
+            fall: 0.0,
+            lock: 0.0,
+            resets: 0,
+            incoming: vec![],
+        }
+    }
+
+    fn instant_soft_drop(&self) -> bool {
+        self.options.sdf >= 41.0
+    }
+
+    // a move or turn that worked, which puts off locking a limited number of times
+    fn moved(&mut self) {
+        if self.field.grounded() && self.resets < self.options.lockresets {
+            self.lock = 0.0;
+            self.resets += 1;
+        }
+    }
+
+    fn shift(&mut self, dx: i8) {
+        if self.field.shift(dx) {
+            self.moved();
+        }
+    }
+
+    // keeps a few bags ahead of what's been spawned, Playfield needs to see the next piece
+    fn refill(&mut self) {
+        while self.field.pieces.len() < self.field.spawned() + 8 {
+            let bag = self.rng.bag();
+            self.field.pieces.extend(bag);
+        }
+    }
+
+    // a new piece coming out gets moved right away by a charged das or an instant soft drop
+    fn new_piece(&mut self) {
+        self.fall = 0.0;
+        self.lock = 0.0;
+        self.resets = 0;
+        if self.dir != 0 && self.das >= self.options.das && self.options.arr == 0.0 {
+            self.field.slide(self.dir);
+        }
+        if self.soft && self.instant_soft_drop() {
+            self.field.sonic_drop();
+        }
+    }
+
+    fn hard_drop(&mut self) -> Result<(), IllegalMove> {
+        let Some(info) = self.field.lock()? else {
+            return Ok(());
+        };
+        // attack cancels garbage that's still on its way first
+        let mut attack = info.attack;
+        while attack > 0 && !self.incoming.is_empty() {
+            let cancelled = attack.min(self.incoming[0].lines);
+            attack -= cancelled;
+            self.incoming[0].lines -= cancelled;
+            if self.incoming[0].lines == 0 {
+                self.incoming.remove(0);
+            }
+        }
+        if info.lines_cleared == 0 {
+            let mut cap = self.options.garbagecap;
+            while cap > 0 && self.incoming.first().is_some_and(|g| g.frame <= self.frame) {
+                let lines = cap.min(self.incoming[0].lines);
+                self.field.add_garbage(lines, self.incoming[0].hole);
+                cap -= lines;
+                self.incoming[0].lines -= lines;
+                if self.incoming[0].lines == 0 {
+                    self.incoming.remove(0);
+                }
+            }
+        }
+        self.refill();
+        self.field.spawn();
+        self.new_piece();
+        Ok(())
+    }
+
+    fn key(&mut self, key: &str, down: bool) -> Result<(), IllegalMove> {
+        match (key, down) {
+            ("moveLeft" | "moveRight", _) => {
+                let dx = if key == "moveLeft" { -1 } else { 1 };
+                if dx < 0 {
+                    self.left = down;
+                } else {
+                    self.right = down;
+                }
+                if down {
+                    self.dir = dx;
+                    self.das = 0.0;
+                    self.arr = 0.0;
+                    self.shift(dx);
+                } else if self.dir == dx {
+                    // the other direction takes over if it's still held, starting its das over
+                    self.dir = match (self.left, self.right) {
+                        (true, _) => -1,
+                        (_, true) => 1,
+                        _ => 0,
+                    };
+                    self.das = 0.0;
+                    self.arr = 0.0;
+                }
+            }
+            ("softDrop", _) => {
+                self.soft = down;
+                if down && self.instant_soft_drop() {
+                    self.field.sonic_drop();
+                }
+            }
+            ("hardDrop", true) => return self.hard_drop(),
+            ("rotateCW" | "rotateCCW" | "rotate180", true) => {
+                let Some(loc) = self.field.active else {
+                    return Ok(());
+                };
+                let to = match key {
+                    "rotateCW" => loc.rotation.rotate_right(),
+                    "rotateCCW" => loc.rotation.rotate_left(),
+                    _ => loc.rotation.rotate_180(),
+                };
+                if self.field.rotate(to) {
+                    self.moved();
+                }
+            }
+            ("hold", true) => {
+                self.refill();
+                if self.field.hold() {
+                    self.new_piece();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, events: &[Value]) -> Result<(), IllegalMove> {
+        for event in events {
+            let frame = event["frame"].as_u64().unwrap_or(self.frame);
+            while self.frame < frame && self.field.active.is_some() {
+                self.tick()?;
+            }
+            let data = &event["data"];
+            match event["type"].as_str() {
+                Some("keydown") => self.key(data["key"].as_str().unwrap_or(""), true)?,
+                Some("keyup") => self.key(data["key"].as_str().unwrap_or(""), false)?,
+                Some("ige") => {
+                    if let Some((lines, hole)) = garbage(data) {
+                        self.incoming.push(Incoming {
+                            frame: frame + self.options.garbagespeed,
+                            lines,
+                            hole,
+                        });
+                    }
+                }
+                Some("end") => break,
+                _ => {}
+            }
+            if self.field.active.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn tick(&mut self) -> Result<(), IllegalMove> {
+        self.frame += 1;
+        if self.field.active.is_none() {
+            return Ok(());
+        }
+        if self.dir != 0 {
+            self.das += 1.0;
+            if self.das >= self.options.das {
+                if self.options.arr == 0.0 {
+                    if self.field.slide(self.dir) {
+                        self.moved();
+                    }
+                } else {
+                    self.arr -= 1.0;
+                    if self.arr <= 0.0 {
+                        self.arr += self.options.arr;
+                        self.shift(self.dir);
+                    }
+                }
+            }
+        }
+
+        if self.frame as f64 > self.options.gmargin {
+            self.g += self.options.gincrease;
+        }
+        if self.soft && self.instant_soft_drop() {
+            self.field.sonic_drop();
+        } else {
+            self.fall += match self.soft {
+                true => self.g * self.options.sdf,
+                false => self.g,
+            };
+            while self.fall >= 1.0 {
+                self.fall -= 1.0;
+                if !self.field.drop() {
+                    self.fall = 0.0;
+                }
+            }
+        }
+
+        if self.field.grounded() {
+            self.lock += 1.0;
+            if self.lock >= self.options.locktime {
+                self.hard_drop()?;
+            }
+        } else {
+            self.lock = 0.0;
+        }
+        Ok(())
+    }
+}
+
+// incoming garbage in an ige event, as (lines, hole column). older replays send it as an attack
+// with `lines`, newer ones as an interaction holding a garbage with `amt`. confirmations are
+// about garbage this player sent
+fn garbage(event: &Value) -> Option<(u32, usize)> {
+    let mut data = event;
+    for _ in 0..3 {
+        if data["type"] == "interaction_confirm" {
+            return None;
+        }
+        if matches!(data["type"].as_str(), Some("garbage" | "attack")) {
+            let lines = data["amt"].as_u64().or_else(|| data["lines"].as_u64())?;
+            let hole = data["column"].as_u64()?;
+            return Some((lines as u32, (hole as usize).min(9)));
+        }
+        data = &data["data"];
+    }
+    None
+}
+
+// a game from a tetr.io replay, rebuilt by playing its inputs back. a .ttr holds one game, a .ttrm
+// holds rounds with a game for every player, picked by `round` and `player`. the board is
+// simulated here rather than read from the replay, so a game where garbage cancels differently
+// than Game's attack table says can drift from what really happened
+pub fn import(replay: &str, round: usize, player: usize) -> Result<ImportedGame, ParseError> {
+    let replay: Value = serde_json::from_str(replay)
+        .map_err(|e| ParseError(format!("replay isn't valid json: {e}")))?;
+    let data = &replay["data"];
+    let game = match data.is_array() {
+        true => &data[round]["replays"][player],
+        false => data,
+    };
+    let events = game["events"]
+        .as_array()
+        .ok_or_else(|| ParseError(format!("no game for round {round} player {player}")))?;
+    let options = events
+        .iter()
+        .find(|e| e["type"] == "full")
+        .map(|e| &e["data"]["options"])
+        .ok_or_else(|| ParseError("replay has no game options".to_string()))?;
+
+    let mut sim = Sim::new(Options::parse(options)?);
+    sim.run(events)
+        .map_err(|e| ParseError(format!("replay doesn't play back, {e}")))?;
+    Ok(sim.field.finish())
+}
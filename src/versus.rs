@@ -0,0 +1,261 @@
+use crate::beam_search::*;
+use crate::data::*;
+use crate::eval::Eval;
+use crate::simulate::parallel_map;
+
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone)]
+pub struct Bot {
+    pub name: String,
+    pub eval: Eval,
+    pub search: SearchConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct VersusConfig {
+    // games played between every pair of bots. who moves first alternates between games
+    pub games: usize,
+    // a game where neither side tops out within this many pieces each is a draw
+    pub max_pieces: usize,
+    // most garbage lines that can come up after a single placement, the rest waits
+    pub garbage_cap: u32,
+    // game g between bots i and j is seeded from seed, i, j and g
+    pub seed: u64,
+    // how many times the results get resampled for the rating confidence intervals
+    pub bootstrap: usize,
+}
+
+impl Default for VersusConfig {
+    fn default() -> Self {
+        Self {
+            games: 10,
+            max_pieces: 1000,
+            garbage_cap: 8,
+            seed: 0,
+            bootstrap: 200,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    // index of the winner in the bots passed to play_match
+    Win(usize),
+    Draw,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct MatchResult {
+    pub outcome: Outcome,
+    // pieces placed by each side
+    pub pieces: [usize; 2],
+    // attack sent by each side, before cancelling
+    pub attack: [u32; 2],
+}
+
+// both bots get the same seeded piece sequence on their own board and take turns placing one
+// piece each. attack first cancels the sender's own incoming garbage, the rest gets queued for
+// the other side, and comes up (capped) after their next placement that doesn't clear a line
+pub fn play_match(bots: [&Bot; 2], config: &VersusConfig, seed: u64, first: usize) -> MatchResult {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let preview = bots.iter().map(|b| b.search.preview).max().unwrap();
+    let bags = (config.max_pieces + preview) as u32 / 7 + 2;
+    let (hold, queue) = gen_queue(bags, &mut rng);
+    let configs = bots.map(|b| SearchConfig {
+        seed: b.search.seed.or(Some(seed)),
+        ..b.search
+    });
+
+    let mut games = [Game::new(Some(hold)); 2];
+    let mut placed = [0usize; 2];
+    let mut pending = [0u32; 2];
+    let mut attack = [0u32; 2];
+
+    for turn in 0..2 * config.max_pieces {
+        let i = (turn + first) % 2;
+        let queue = &queue[placed[i]..];
+        let Ok(loc) = search(&games[i], queue, &bots[i].eval, &configs[i]) else {
+            return MatchResult {
+                outcome: Outcome::Win(1 - i),
+                pieces: placed,
+                attack,
+            };
+        };
+        let info = games[i].advance(queue[0], loc);
+        placed[i] += 1;
+        attack[i] += info.attack;
+
+        let cancelled = info.attack.min(pending[i]);
+        pending[i] -= cancelled;
+        pending[1 - i] += info.attack - cancelled;
+
+        if info.lines_cleared == 0 && pending[i] > 0 {
+            let lines = pending[i].min(config.garbage_cap);
+            games[i].board.add_garbage(lines, rng.random_range(0..10));
+            pending[i] -= lines;
+        }
+    }
+
+    MatchResult {
+        outcome: Outcome::Draw,
+        pieces: placed,
+        attack,
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct GameRecord {
+    pub a: usize,
+    pub b: usize,
+    // 1 if a won, 0 if b won, 0.5 for a draw
+    pub score: f32,
+    pub result: MatchResult,
+}
+
+// every pair of bots plays config.games games, spread over all cores
+pub fn round_robin(bots: &[Bot], config: &VersusConfig) -> Vec<GameRecord> {
+    let mut jobs = vec![];
+    for a in 0..bots.len() {
+        for b in a + 1..bots.len() {
+            for g in 0..config.games {
+                jobs.push((a, b, g));
+            }
+        }
+    }
+
+    parallel_map(jobs.len(), |job| {
+        let (a, b, g) = jobs[job];
+        let seed = config
+            .seed
+            .wrapping_mul(0x9e3779b97f4a7c15)
+            .wrapping_add((a * bots.len() + b) as u64 * 0x10000 + g as u64);
+        let result = play_match([&bots[a], &bots[b]], config, seed, g % 2);
+        let score = match result.outcome {
+            Outcome::Win(0) => 1.0,
+            Outcome::Win(_) => 0.0,
+            Outcome::Draw => 0.5,
+        };
+        GameRecord {
+            a,
+            b,
+            score,
+            result,
+        }
+    })
+}
+
+// bradley-terry fit on the elo scale, centered on 0. every pair also gets one virtual draw, which
+// keeps a bot that won every game from running off to infinity
+pub fn elo(n: usize, games: &[GameRecord]) -> Vec<f64> {
+    if n < 2 {
+        return vec![0.0; n];
+    }
+    let mut wins = vec![0.0; n];
+    let mut count = vec![vec![0.0; n]; n];
+    for g in games {
+        wins[g.a] += g.score as f64;
+        wins[g.b] += 1.0 - g.score as f64;
+        count[g.a][g.b] += 1.0;
+        count[g.b][g.a] += 1.0;
+    }
+    for a in 0..n {
+        for b in a + 1..n {
+            wins[a] += 0.5;
+            wins[b] += 0.5;
+            count[a][b] += 1.0;
+            count[b][a] += 1.0;
+        }
+    }
+
+    // minorization-maximization, converges for any connected set of results
+    let mut strength = vec![1.0; n];
+    for _ in 0..10000 {
+        let mut next: Vec<f64> = (0..n)
+            .map(|i| {
+                let denom: f64 = (0..n)
+                    .filter(|&j| j != i)
+                    .map(|j| count[i][j] / (strength[i] + strength[j]))
+                    .sum();
+                wins[i] / denom
+            })
+            .collect();
+        let log_mean = next.iter().map(|s: &f64| s.ln()).sum::<f64>() / n as f64;
+        for s in &mut next {
+            *s /= log_mean.exp();
+        }
+        let change = next
+            .iter()
+            .zip(&strength)
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max);
+        strength = next;
+        if change < 1e-10 {
+            break;
+        }
+    }
+    strength.iter().map(|s| 400.0 * s.log10()).collect()
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rating {
+    pub name: String,
+    pub elo: f64,
+    // 95% confidence interval from resampling the games
+    pub low: f64,
+    pub high: f64,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+pub fn ratings(bots: &[Bot], games: &[GameRecord], config: &VersusConfig) -> Vec<Rating> {
+    let n = bots.len();
+    let elos = elo(n, games);
+
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut samples: Vec<Vec<f64>> = vec![vec![]; n];
+    for _ in 0..config.bootstrap {
+        let resampled: Vec<GameRecord> = (0..games.len())
+            .filter_map(|_| games.choose(&mut rng).copied())
+            .collect();
+        for (i, e) in elo(n, &resampled).into_iter().enumerate() {
+            samples[i].push(e);
+        }
+    }
+
+    let mut ratings: Vec<Rating> = (0..n)
+        .map(|i| {
+            let s = &mut samples[i];
+            s.sort_by(f64::total_cmp);
+            let percentile = |p: f64| {
+                s.get(((s.len() as f64 - 1.0) * p).round() as usize)
+                    .copied()
+                    .unwrap_or(elos[i])
+            };
+            let (mut wins, mut losses, mut draws) = (0, 0, 0);
+            for g in games.iter().filter(|g| g.a == i || g.b == i) {
+                match (g.result.outcome, g.a == i) {
+                    (Outcome::Draw, _) => draws += 1,
+                    (Outcome::Win(0), true) | (Outcome::Win(1), false) => wins += 1,
+                    _ => losses += 1,
+                }
+            }
+            Rating {
+                name: bots[i].name.clone(),
+                elo: elos[i],
+                low: percentile(0.025),
+                high: percentile(0.975),
+                wins,
+                losses,
+                draws,
+            }
+        })
+        .collect();
+    ratings.sort_by(|a, b| b.elo.total_cmp(&a.elo));
+    ratings
+}